@@ -0,0 +1,94 @@
+use std::io::Write;
+
+/// A writer that removes ANSI escape sequences before passing output on, so that no escape codes
+/// reach a pipe or file no matter which code path produced them. Sequences split across writes
+/// are handled, since the parser state is kept between calls.
+pub struct AnsiStrip<W: Write> {
+    inner: W,
+    enabled: bool,
+    state: EscapeState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    /// Plain output
+    Text,
+    /// After an `ESC`
+    Escape,
+    /// Inside a control sequence (`ESC [`), which ends with a byte in `0x40..=0x7e`
+    Csi,
+    /// Inside an operating system command (`ESC ]`), which ends with `BEL` or `ESC \`
+    Osc,
+    /// After an `ESC` inside an operating system command
+    OscEscape,
+}
+
+impl<W: Write> AnsiStrip<W> {
+    /// Wrap a writer. When `enabled` is false, output is passed through untouched.
+    pub fn new(inner: W, enabled: bool) -> Self {
+        AnsiStrip {
+            inner,
+            enabled,
+            state: EscapeState::Text,
+        }
+    }
+}
+
+impl<W: Write> Write for AnsiStrip<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.enabled {
+            return self.inner.write(buf);
+        }
+        let mut text = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            self.state = match (self.state, byte) {
+                (EscapeState::Text, 0x1b) => EscapeState::Escape,
+                (EscapeState::Text, _) => {
+                    text.push(byte);
+                    EscapeState::Text
+                }
+                (EscapeState::Escape, b'[') => EscapeState::Csi,
+                (EscapeState::Escape, b']') => EscapeState::Osc,
+                // Any other escape is a single character sequence
+                (EscapeState::Escape, _) => EscapeState::Text,
+                (EscapeState::Csi, 0x40..=0x7e) => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::Osc, 0x07) => EscapeState::Text,
+                (EscapeState::Osc, 0x1b) => EscapeState::OscEscape,
+                (EscapeState::Osc, _) => EscapeState::Osc,
+                (EscapeState::OscEscape, b'\\') => EscapeState::Text,
+                (EscapeState::OscEscape, _) => EscapeState::Osc,
+            };
+        }
+        self.inner.write_all(&text)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(chunks: &[&[u8]]) -> String {
+        let mut out = AnsiStrip::new(Vec::new(), true);
+        for chunk in chunks {
+            out.write_all(chunk).unwrap();
+        }
+        String::from_utf8(out.inner).unwrap()
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip(&[b"\x1b[38;5;9mred\x1b[0m plain"]), "red plain");
+        assert_eq!(strip(&[b"\x1b[1", b";31mbold", b"\x1b", b"[0m"]), "bold");
+        assert_eq!(strip(&[b"\x1b]0;title\x07text\x1b]8;;x\x1b\\"]), "text");
+
+        let mut passthrough = AnsiStrip::new(Vec::new(), false);
+        passthrough.write_all(b"\x1b[0m").unwrap();
+        assert_eq!(passthrough.inner, b"\x1b[0m");
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use serde_json::json;
+
+use crate::{hash::HashAlgo, inscription::Inscription};
+
+/// Streams inscriptions into a tar archive as they are extracted, so that memory use stays flat
+/// no matter how many inscriptions are archived. A `manifest.json` entry describing every
+/// inscription is appended when the archive is finished.
+pub struct TarArchive {
+    builder: tar::Builder<ArchiveWriter>,
+    manifest: Vec<serde_json::Value>,
+    hash: Option<HashAlgo>,
+}
+
+impl TarArchive {
+    /// Create the archive. Paths ending in `.tar.gz` or `.tgz` are gzip compressed. With a hash
+    /// algorithm, each manifest entry also records the hash of the content.
+    pub fn create(path: &PathBuf, hash: Option<HashAlgo>) -> anyhow::Result<Self> {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() && !dir.exists() => {
+                std::fs::create_dir_all(dir)?
+            }
+            _ => {}
+        }
+        let file = File::create(path)?;
+        let writer = if is_gzip(path) {
+            ArchiveWriter::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            ArchiveWriter::Plain(file)
+        };
+        Ok(TarArchive {
+            builder: tar::Builder::new(writer),
+            manifest: Vec::new(),
+            hash,
+        })
+    }
+
+    pub fn append(&mut self, inscription: &Inscription) -> anyhow::Result<()> {
+        let fname = inscription.file_name();
+        append_file(&mut self.builder, &fname, &inscription.data)?;
+        let mut entry = json!({
+            "inscription_id": inscription.inscription_id(),
+            "mime": inscription.mime,
+            "size": inscription.data.len(),
+            "file": fname,
+        });
+        if let Some(algo) = self.hash {
+            entry[algo.to_string()] = algo.digest(&inscription.data).into();
+        }
+        self.manifest.push(entry);
+        Ok(())
+    }
+
+    /// Write the manifest and flush the archive to disk
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        let manifest = serde_json::to_vec_pretty(&self.manifest)?;
+        append_file(&mut self.builder, "manifest.json", &manifest)?;
+        self.builder.into_inner()?.finish()
+    }
+}
+
+fn append_file(
+    builder: &mut tar::Builder<ArchiveWriter>,
+    name: &str,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+fn is_gzip(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+enum ArchiveWriter {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl ArchiveWriter {
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            ArchiveWriter::Plain(mut file) => file.flush()?,
+            ArchiveWriter::Gzip(encoder) => encoder.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ArchiveWriter::Plain(file) => file.write(buf),
+            ArchiveWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ArchiveWriter::Plain(file) => file.flush(),
+            ArchiveWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -1,19 +1,35 @@
-use std::{io::stdout, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashSet, fmt::Display, io::stdout, path::PathBuf, str::FromStr, sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail};
-use bitcoin::{BlockHash, Txid};
+use bitcoin::{BlockHash, Network, Txid};
 use bitcoincore_rpc::Auth;
 use crossterm::tty::IsTty;
 use directories::BaseDirs;
 
-use crate::{filter::Filter, inscription::InscriptionId};
+use crate::{
+    filter::Filter,
+    hash::HashAlgo,
+    imagehash::ImageHash,
+    inscription::{
+        Fit, ImageLayout, Inscription, InscriptionId, ParseOptions, Preference, PrintOptions,
+    },
+    theme::Theme,
+};
 
 #[derive(clap::Parser, Debug)]
 pub struct Args {
-    /// Host name/IP address of Bitcoin full node
+    /// Host name/IP address of Bitcoin full node, optionally with a port. Without one, the
+    /// default RPC port of --network is used
     #[arg(long, env = "BITCOIN_HOST")]
     pub host: Option<String>,
 
+    /// Bitcoin network of the node [mainnet, testnet, signet, regtest]
+    #[arg(long, env = "BITCOIN_NETWORK", default_value = "mainnet", value_parser = parse_network)]
+    pub network: Network,
+
     /// Username for RPC user (if applicable)
     #[arg(long, env = "BITCOIN_USER")]
     pub user: Option<String>,
@@ -26,29 +42,146 @@ pub struct Args {
     #[arg(long, env = "BITCOIN_COOKIE")]
     pub cookie: Option<PathBuf>,
 
+    /// Seconds to wait for the node or Esplora server to answer a request
+    #[arg(long, env = "ORTTY_RPC_TIMEOUT", default_value_t = 15, value_parser = clap::value_parser!(u64).range(1..))]
+    pub rpc_timeout: u64,
+
+    /// Times to retry a request that timed out or failed to connect, waiting twice as long
+    /// before each retry. Errors such as bad credentials fail right away
+    #[arg(long, env = "ORTTY_RPC_RETRIES", default_value_t = 3)]
+    pub rpc_retries: u32,
+
+    /// Base URL of an Esplora server to fetch blocks and transactions from instead of a full
+    /// node, such as https://blockstream.info/api
+    #[arg(long, env = "ORTTY_ESPLORA")]
+    pub esplora: Option<String>,
+
+    /// Load environment variables from this file. May be specified multiple times, files are
+    /// loaded in order and never overwrite variables that are already set
+    #[arg(long, global = true)]
+    pub env_file: Vec<PathBuf>,
+
+    /// Color scheme for printing JSON [default, monokai, solarized]
+    #[arg(long, global = true, default_value_t)]
+    pub theme: Theme,
+
+    /// Minimum ratio (0.0 - 1.0) of printable characters for UTF-8 data to be treated as text.
+    /// Data below the threshold is treated as binary
+    #[arg(
+        long,
+        global = true,
+        default_value_t = ParseOptions::default().text_threshold,
+        value_parser = parse_ratio
+    )]
+    pub text_threshold: f64,
+
+    /// Only look for inscriptions in taproot script path spends, skipping other transactions
+    /// without parsing them
+    #[arg(long, global = true)]
+    pub taproot_only: bool,
+
+    /// Cut off inscription bodies larger than this many bytes, marking them as truncated
+    #[arg(
+        long,
+        global = true,
+        default_value_t = ParseOptions::default().max_inscription_bytes
+    )]
+    pub max_inscription_bytes: usize,
+
+    /// When the declared mime type and the content disagree about what an inscription is, trust
+    /// the mime type. For example, bytes declared as text are shown as text even if they are an
+    /// image
+    #[arg(long, global = true, conflicts_with = "prefer_content")]
+    pub prefer_mime: bool,
+
+    /// When the declared mime type and the content disagree about what an inscription is, trust
+    /// the content. Images are detected before text, and HTML by its markup
+    #[arg(long, global = true)]
+    pub prefer_content: bool,
+
+    /// Render short text inscriptions as a QR code
+    #[arg(long, global = true)]
+    pub qr: bool,
+
+    /// Open audio inscriptions in the system's default player as they are printed
+    #[arg(long, global = true)]
+    pub play: bool,
+
+    /// Syntax highlight code and markup, such as JavaScript, CSS and HTML, based on the declared
+    /// mime type. Colors follow --theme
+    #[arg(long, global = true)]
+    pub highlight: bool,
+
+    /// Render images in a box this many terminal rows tall, so tall images stay in view
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub image_height: Option<u32>,
+
+    /// Show only this frame of animated GIFs, counting from 1, instead of playing them. GIFs with
+    /// fewer frames show their last one
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frame: Option<u32>,
+
+    /// Bytes per row when printing binary inscriptions as a hexdump [8, 16, 32]. With --raw they
+    /// are printed as a single line of hex instead
+    #[arg(long, global = true, value_parser = parse_hex_width)]
+    pub hex_width: Option<usize>,
+
+    /// How images are fitted into the box set by --image-height [contain, cover, stretch].
+    /// `contain` keeps the whole image, adding bars. `cover` crops it to fill the box
+    #[arg(long, global = true, default_value_t, requires = "image_height")]
+    pub fit: Fit,
+
+    /// URL that --web opens for an inscription, with `{id}` replaced by the inscription id
+    #[arg(long, global = true, env = "ORTTY_EXPLORER", default_value_t)]
+    pub explorer_url: ExplorerUrl,
+
+    /// Show sizes in raw bytes instead of human-readable units
+    #[arg(long, global = true)]
+    pub bytes: bool,
+
+    /// Strip ANSI escape codes from output. This is always done when stdout is not a terminal
+    #[arg(long, global = true)]
+    pub ansi_strip: bool,
+
+    /// Print additional diagnostic information to stderr
+    #[arg(long, global = true)]
+    pub verbose: bool,
+
+    /// Report errors on stderr as a JSON object with `error` and `code` fields, instead of a
+    /// message
+    #[arg(long, global = true)]
+    pub errors_json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 impl Args {
     pub fn find_cookie(&self) -> Option<PathBuf> {
+        // Bitcoin Core keeps the cookie of other networks in a subfolder of its data folder
+        let network_dir = match self.network {
+            Network::Testnet => "testnet3",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+            _ => "",
+        };
         if let Some(bd) = BaseDirs::new() {
             let paths = [
-                bd.home_dir().join(".bitcoin").join("cookie"),
-                bd.config_dir().join("bitcoin").join("cookie"),
-                bd.config_local_dir().join("bitcoin").join("cookie"),
-                bd.data_dir().join("bitcoin").join("cookie"),
-            ];
+                bd.home_dir().join(".bitcoin"),
+                bd.config_dir().join("bitcoin"),
+                bd.config_local_dir().join("bitcoin"),
+                bd.data_dir().join("bitcoin"),
+            ]
+            .map(|dir| dir.join(network_dir).join("cookie"));
             return paths.into_iter().find(|p| p.exists());
         }
         None
     }
 
+    /// The node's RPC URL, with the network's default port added if the host has none
     pub fn rpc_host(&self) -> String {
-        match &self.host {
-            Some(host) => host.clone(),
-            None => "localhost".into(),
-        }
+        let host = self.host.as_deref().unwrap_or("localhost");
+        with_default_port(host, rpc_port(self.network))
     }
 
     pub fn rpc_auth(&self) -> anyhow::Result<Auth> {
@@ -75,6 +208,21 @@ impl Args {
 
     pub fn scan_mode(&self) -> anyhow::Result<ScanMode> {
         let mode = match &self.command {
+            Commands::Scan {
+                from_height: Some(from),
+                to_height: Some(to),
+                filter,
+                ..
+            } => {
+                if from > to {
+                    bail!("--from-height must not be above --to-height");
+                }
+                if to - from >= MAX_RANGE_BLOCKS {
+                    let count = (to - from).saturating_add(1);
+                    bail!("Cannot scan {count} blocks at once, the limit is {MAX_RANGE_BLOCKS}");
+                }
+                ScanMode::BlockRange(*from, *to, filter.clone())
+            }
             Commands::Scan {
                 block: Some(block),
                 tx: None,
@@ -92,6 +240,158 @@ impl Args {
         Ok(mode)
     }
 
+    pub fn reads_stdin(&self) -> bool {
+        match &self.command {
+            Commands::Scan { from_stdin, .. } => *from_stdin,
+            _ => false,
+        }
+    }
+
+    pub fn scans_mempool(&self) -> bool {
+        match &self.command {
+            Commands::Scan { mempool, .. } => *mempool,
+            _ => false,
+        }
+    }
+
+    pub fn mempool_limit(&self) -> Option<usize> {
+        match &self.command {
+            Commands::Scan { limit, .. } => *limit,
+            _ => None,
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        match &self.command {
+            Commands::Scan { deadline, .. } => deadline.map(Duration::from_secs),
+            _ => None,
+        }
+    }
+
+    pub fn log_file(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan { log_file, .. } => log_file.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn raw_witness_hex(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan {
+                raw_witness_hex, ..
+            } => raw_witness_hex.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn record(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan { record, .. } => record.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn replay(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan { replay, .. } => replay.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn blocks(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan { blocks, .. } => blocks.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn max_block_bytes(&self) -> Option<usize> {
+        match &self.command {
+            Commands::Scan {
+                max_block_bytes, ..
+            } => *max_block_bytes,
+            _ => None,
+        }
+    }
+
+    pub fn force(&self) -> bool {
+        match &self.command {
+            Commands::Scan { force, .. } => *force,
+            _ => false,
+        }
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        match &self.command {
+            Commands::Scan { filter, .. } => filter,
+            Commands::Explore { filter, .. } => filter,
+            Commands::Has { filter, .. } => filter,
+            _ => &[],
+        }
+    }
+
+    /// The lowest and highest block heights the explorer may show
+    pub fn height_bounds(&self) -> anyhow::Result<(u64, Option<u64>)> {
+        match &self.command {
+            Commands::Explore {
+                min_height,
+                max_height,
+                ..
+            } => match (min_height, max_height) {
+                (Some(min), Some(max)) if min > max => {
+                    bail!("--min-height must not be above --max-height")
+                }
+                _ => Ok((min_height.unwrap_or_default(), *max_height)),
+            },
+            _ => Ok((0, None)),
+        }
+    }
+
+    /// The algorithm to hash content with, if `--with-hash` is set
+    pub fn content_hash(&self) -> Option<HashAlgo> {
+        match &self.command {
+            Commands::Scan {
+                with_hash: true,
+                hash_algo,
+                ..
+            } => Some(*hash_algo),
+            _ => None,
+        }
+    }
+
+    pub fn hash_allowlist(&self) -> Option<&HashSet<String>> {
+        match &self.command {
+            Commands::Scan { hash_allowlist, .. } => hash_allowlist.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn image_hash(&self) -> bool {
+        match &self.command {
+            Commands::Scan { image_hash, .. } => *image_hash,
+            _ => false,
+        }
+    }
+
+    /// The image hash to match with `--similar-to`, along with the maximum distance allowed
+    pub fn similar_to(&self) -> Option<(ImageHash, u32)> {
+        match &self.command {
+            Commands::Scan {
+                similar_to,
+                max_distance,
+                ..
+            } => similar_to.map(|hash| (hash, *max_distance)),
+            _ => None,
+        }
+    }
+
+    pub fn exclude_mime(&self) -> &[String] {
+        match &self.command {
+            Commands::Scan { exclude_mime, .. } => exclude_mime,
+            _ => &[],
+        }
+    }
+
     pub fn extract(&self) -> Option<&PathBuf> {
         match &self.command {
             Commands::Scan { extract, .. } => extract.as_ref(),
@@ -99,6 +399,52 @@ impl Args {
         }
     }
 
+    pub fn canonicalize_json(&self) -> bool {
+        match &self.command {
+            Commands::Scan {
+                canonicalize_json, ..
+            } => *canonicalize_json,
+            _ => false,
+        }
+    }
+
+    pub fn verify_extract(&self) -> bool {
+        match &self.command {
+            Commands::Scan { verify_extract, .. } => *verify_extract,
+            _ => false,
+        }
+    }
+
+    pub fn include_transfers(&self) -> bool {
+        match &self.command {
+            Commands::Scan {
+                include_transfers, ..
+            } => *include_transfers,
+            _ => false,
+        }
+    }
+
+    pub fn keep_encoding(&self) -> bool {
+        match &self.command {
+            Commands::Scan { keep_encoding, .. } => *keep_encoding,
+            _ => false,
+        }
+    }
+
+    pub fn organize(&self) -> bool {
+        match &self.command {
+            Commands::Scan { organize, .. } => *organize,
+            _ => false,
+        }
+    }
+
+    pub fn extract_tar(&self) -> Option<&PathBuf> {
+        match &self.command {
+            Commands::Scan { extract_tar, .. } => extract_tar.as_ref(),
+            _ => None,
+        }
+    }
+
     pub fn web(&self) -> Option<bool> {
         match &self.command {
             Commands::Scan { web, .. } => Some(*web),
@@ -113,6 +459,105 @@ impl Args {
         }
     }
 
+    pub fn print_vout(&self) -> bool {
+        match &self.command {
+            Commands::Scan { print_vout, .. } => *print_vout,
+            _ => false,
+        }
+    }
+
+    /// The range of results on the requested `--page`
+    pub fn page(&self) -> Option<std::ops::Range<usize>> {
+        match &self.command {
+            Commands::Scan {
+                page: Some(page),
+                page_size,
+                ..
+            } => {
+                let start = (*page as usize - 1).saturating_mul(*page_size as usize);
+                Some(start..start.saturating_add(*page_size as usize))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        match &self.command {
+            Commands::Scan { format, .. } => *format,
+            _ => OutputFormat::Text,
+        }
+    }
+
+    pub fn sort(&self) -> Option<SortKey> {
+        match &self.command {
+            Commands::Scan { sort, .. } => *sort,
+            _ => None,
+        }
+    }
+
+    pub fn count(&self) -> bool {
+        matches!(self.command, Commands::Scan { count: true, .. })
+    }
+
+    pub fn separators(&self) -> bool {
+        !matches!(
+            self.command,
+            Commands::Scan {
+                no_separators: true,
+                ..
+            }
+        )
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            text_threshold: self.text_threshold,
+            taproot_only: self.taproot_only,
+            max_inscription_bytes: self.max_inscription_bytes,
+            prefer: match (self.prefer_mime, self.prefer_content) {
+                (true, _) => Preference::Mime,
+                (_, true) => Preference::Content,
+                _ => Preference::Balanced,
+            },
+        }
+    }
+
+    pub fn print_options(&self) -> PrintOptions {
+        PrintOptions {
+            raw: self.raw(),
+            theme: self.theme,
+            qr: self.qr,
+            highlight: self.highlight,
+            play: self.play,
+            frame: self.frame,
+            hex_width: self.hex_width,
+            strip_ansi: self.strip_ansi(),
+            layout: ImageLayout {
+                height: self.image_height,
+                fit: self.fit,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Whether ANSI escape codes should be removed from output
+    pub fn strip_ansi(&self) -> bool {
+        self.ansi_strip || !stdout().is_tty()
+    }
+
+    pub fn ord_url(&self) -> Option<&str> {
+        match &self.command {
+            Commands::Inscription { ord_url, .. } => ord_url.as_deref(),
+            Commands::Info { ord_url, .. } => ord_url.as_deref(),
+            Commands::Sat { ord_url, .. } => Some(ord_url),
+            _ => None,
+        }
+    }
+
+    pub fn explain(&self) -> bool {
+        matches!(self.command, Commands::Inscription { explain: true, .. })
+    }
+
     pub fn raw(&self) -> bool {
         // If it's not a TTY, then never print colored text
         if !stdout().is_tty() {
@@ -127,6 +572,8 @@ impl Args {
     }
 }
 
+// Only one command is ever parsed, so the size of the scan options doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(clap::Subcommand, Debug)]
 pub enum Commands {
     /// Scan a block and/or tx in order to view the embedded inscriptions. Specifying only a
@@ -143,15 +590,153 @@ pub enum Commands {
         #[arg(long)]
         tx: Option<Txid>,
 
-        /// Filter inscriptions by type [text, json, brc20, image]
+        /// Write the full witness stack of every input of the scanned transaction as hex to this
+        /// file, or to stdout if `-`, including signatures and control blocks
+        #[arg(long, requires = "tx")]
+        raw_witness_hex: Option<PathBuf>,
+
+        /// Read newline delimited raw transaction hex from stdin, printing inscriptions as each
+        /// transaction is parsed. Runs until stdin is closed
+        #[arg(long, conflicts_with_all = ["block", "tx"])]
+        from_stdin: bool,
+
+        /// Stop scanning after this many seconds, keeping the inscriptions found so far. Exits
+        /// with code 124 when the deadline is reached
+        #[arg(long)]
+        deadline: Option<u64>,
+
+        /// Append a JSON line describing each scan (time, target, inscription count and
+        /// filters) to this file
+        #[arg(long)]
+        log_file: Option<PathBuf>,
+
+        /// Read newline delimited block hashes or heights from this file, or from stdin if `-`,
+        /// and scan each block in turn
+        #[arg(long, conflicts_with_all = ["block", "tx", "from_stdin"])]
+        blocks: Option<PathBuf>,
+
+        /// Scan every block from this height up to --to-height, inclusive
+        #[arg(
+            long,
+            requires = "to_height",
+            conflicts_with_all = ["block", "tx", "from_stdin", "blocks"]
+        )]
+        from_height: Option<u64>,
+
+        /// Last block height to scan with --from-height
+        #[arg(long, requires = "from_height")]
+        to_height: Option<u64>,
+
+        /// Scan the unconfirmed transactions in the node's mempool, printing inscriptions as each
+        /// transaction is parsed
+        #[arg(
+            long,
+            conflicts_with_all = ["block", "tx", "from_stdin", "blocks", "from_height"]
+        )]
+        mempool: bool,
+
+        /// Append every scanned transaction, and how its inscriptions were classified, to this
+        /// transcript file. It can be replayed with --replay without a node, such as to attach
+        /// to a bug report
+        #[arg(long, conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// Scan the transactions in a transcript written by --record instead of asking the node,
+        /// noting on stderr any inscription that is now classified differently
+        #[arg(
+            long,
+            conflicts_with_all = ["block", "tx", "from_stdin", "blocks", "from_height", "mempool"]
+        )]
+        replay: Option<PathBuf>,
+
+        /// Scan at most this many transactions with --mempool
+        #[arg(long, requires = "mempool")]
+        limit: Option<usize>,
+
+        /// Also report inscriptions moved by the scanned blocks, not just those revealed. This
+        /// fetches the transaction behind every input, which is one extra RPC call per spent
+        /// transaction and slow for full blocks. Requires `txindex=1`. Only inscriptions on the
+        /// first output of their reveal are followed, so those a pointer places on another output
+        /// are not reported
+        #[arg(long, conflicts_with_all = ["tx", "from_stdin", "mempool", "replay"])]
+        include_transfers: bool,
+
+        /// Refuse to scan blocks larger than this many bytes, to protect low memory machines
         #[arg(long)]
+        max_block_bytes: Option<usize>,
+
+        /// Scan blocks even if they are over --max-block-bytes
+        #[arg(long, requires = "max_block_bytes")]
+        force: bool,
+
+        /// Filter inscriptions by type [text, json, brc20, brc20-valid, html, image,
+        /// metaprotocol=NAME, mime:PATTERN, has-parent, no-parent, parent:ID]. Prefix a filter with `!` or `not:` to exclude what it
+        /// matches: inscriptions must match any positive filter and no negated one, as in
+        /// `text,!brc20`. Defaults to the comma separated list in `ORTTY_FILTERS`, if set
+        #[arg(long, env = "ORTTY_FILTERS", value_delimiter = ',')]
         filter: Vec<Filter>,
 
+        /// Exclude inscriptions whose mime type matches this glob pattern, such as `application/*`.
+        /// Matching is case insensitive. Exclusions are applied after `--filter`, so an excluded
+        /// mime type is dropped even if it matches a filter
+        #[arg(long)]
+        exclude_mime: Vec<String>,
+
+        /// Print a hash of each inscription's content, and add it to the `--extract-tar` manifest
+        #[arg(long)]
+        with_hash: bool,
+
+        /// Hash algorithm for `--with-hash` [sha256, blake3]
+        #[arg(long, default_value_t, requires = "with_hash")]
+        hash_algo: HashAlgo,
+
+        /// Only keep inscriptions whose content sha256 is listed in this file, one hex hash per
+        /// line. Applies on top of the other filters
+        #[arg(long, value_parser = read_hash_allowlist)]
+        hash_allowlist: Option<HashSet<String>>,
+
+        /// Print a perceptual hash of each image inscription, for finding similar images
+        #[arg(long)]
+        image_hash: bool,
+
+        /// Only show image inscriptions whose perceptual hash is within `--max-distance` bits of
+        /// this hash
+        #[arg(long)]
+        similar_to: Option<ImageHash>,
+
+        /// Maximum number of differing bits for `--similar-to`
+        #[arg(long, default_value_t = 10, requires = "similar_to")]
+        max_distance: u32,
+
         /// Extract inscriptions to this folder
         #[arg(long)]
         extract: Option<PathBuf>,
 
-        /// View the inscription on the web
+        /// Place extracted inscriptions into subdirectories of the extract folder by kind: images,
+        /// text, json, html and binary
+        #[arg(long, requires = "extract")]
+        organize: bool,
+
+        /// Write extracted JSON in canonical form, with sorted keys and no insignificant
+        /// whitespace. This changes the bytes from the on-chain original
+        #[arg(long)]
+        canonicalize_json: bool,
+
+        /// Decode extracted images again after writing them, warning about any that are corrupt
+        #[arg(long, requires = "extract")]
+        verify_extract: bool,
+
+        /// Write gzip encoded inscriptions as they are stored on chain, with a .gz suffix,
+        /// instead of decompressing them
+        #[arg(long, requires = "extract")]
+        keep_encoding: bool,
+
+        /// Extract inscriptions into this tar archive, along with a manifest.json describing them.
+        /// The archive is gzip compressed if the file name ends in .tar.gz or .tgz
+        #[arg(long)]
+        extract_tar: Option<PathBuf>,
+
+        /// View the inscription on the web, at --explorer-url
         #[arg(long)]
         web: bool,
 
@@ -159,29 +744,185 @@ pub enum Commands {
         #[arg(long)]
         inscription_id: bool,
 
+        /// Print the transaction input each inscription was found in, as `<TXID>:<INPUT>`
+        #[arg(long, alias = "print-txid")]
+        print_vout: bool,
+
         /// Prints JSON as unformatted plain text
         #[arg(long)]
         raw: bool,
+
+        /// Sort inscriptions by [size, mime, kind]. Size sorts largest first. Sorting requires
+        /// every result to be collected before anything is printed
+        #[arg(long)]
+        sort: Option<SortKey>,
+
+        /// Only output this page of the results, counting from 1. Pages are taken after filtering
+        /// and sorting, so keep the other options the same to walk through the pages
+        #[arg(
+            long,
+            conflicts_with_all = ["from_stdin", "blocks", "from_height", "mempool", "replay"],
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        page: Option<u64>,
+
+        /// Print statistics about the matching inscriptions instead of the inscriptions: how many
+        /// there are of each kind, their total size and the largest one
+        #[arg(
+            long,
+            conflicts_with_all = ["from_stdin", "blocks", "from_height", "mempool", "replay", "extract", "extract_tar", "format"]
+        )]
+        count: bool,
+
+        /// Print the numbers in `--count` without thousands separators, for scripts
+        #[arg(long, requires = "count")]
+        no_separators: bool,
+
+        /// Number of inscriptions on each page
+        #[arg(long, default_value_t = 50, requires = "page", value_parser = clap::value_parser!(u64).range(1..))]
+        page_size: u64,
+
+        /// Output format [text, nostr, json]. `nostr` prints text inscriptions as one unsigned
+        /// Nostr event per line and skips other content. `json` prints a single JSON array of every
+        /// inscription once the scan is done, with its content in base64
+        #[arg(long, visible_alias = "output", default_value_t, conflicts_with_all = ["extract", "extract_tar"])]
+        format: OutputFormat,
     },
 
     /// Explore the blockchain interactively
-    Explore,
+    Explore {
+        /// Initial inscription filters [text, json, brc20, brc20-valid, html, image,
+        /// metaprotocol=NAME, mime:PATTERN, has-parent, no-parent, parent:ID]. Defaults to the comma separated list in
+        /// `ORTTY_FILTERS`, or every filter if neither is set
+        #[arg(long, env = "ORTTY_FILTERS", value_delimiter = ',')]
+        filter: Vec<Filter>,
+
+        /// Lowest block height the block browser will page down to
+        #[arg(long)]
+        min_height: Option<u64>,
+
+        /// Highest block height the block browser will start from, instead of the chain tip
+        #[arg(long)]
+        max_height: Option<u64>,
+    },
+
+    /// Check the connection to the node and whether it is configured correctly for ortty
+    Doctor,
 
     /// View a single inscription by inscription id. Requires node with txindex=1
     Inscription {
-        inscription_id: InscriptionId,
+        #[arg(required_unless_present = "id_file")]
+        inscription_id: Option<InscriptionId>,
+
+        /// Read inscription ids from a file, one per line. A line may also specify a range of
+        /// inscriptions in one transaction, such as `<txid>i0-3`
+        #[arg(long)]
+        id_file: Option<PathBuf>,
 
         /// Prints JSON as unformatted plain text
         #[arg(long)]
         raw: bool,
+
+        /// Describe how the content was classified before printing it
+        #[arg(long)]
+        explain: bool,
+
+        /// Write the raw inscription content to this file descriptor instead of printing it,
+        /// leaving stdout for status messages. Unix only
+        #[arg(long, conflicts_with = "explain")]
+        output_fd: Option<u32>,
+
+        /// Fetch content from an ord server's `/content` endpoint instead of the node. Only the
+        /// content and its media type are available in this mode
+        #[arg(long, env = "ORD_URL")]
+        ord_url: Option<String>,
+
+        /// Only print the media type and size of each inscription, as reported by the ord
+        /// server, without downloading the content
+        #[arg(long, requires = "ord_url", conflicts_with_all = ["explain", "output_fd"])]
+        head_only: bool,
+    },
+
+    /// Show an inscription's metadata, such as its media type, size and detected kind
+    Info {
+        inscription_id: InscriptionId,
+
+        /// Fetch content from an ord server's `/content` endpoint instead of the node. Only the
+        /// content and its media type are available in this mode
+        #[arg(long, env = "ORD_URL")]
+        ord_url: Option<String>,
+
+        /// Print the inscription's delegate and parent links as a tree instead, fetching each
+        /// linked inscription from the node
+        #[arg(long, conflicts_with = "ord_url")]
+        tree: bool,
+    },
+
+    /// View the inscriptions on a sat, by sat name or number. Requires an ord server
+    Sat {
+        /// Sat name, such as `nvtdijuwxlp`, or sat number
+        sat: String,
+
+        /// Base URL of the ord server used to look up the sat
+        #[arg(long, env = "ORD_URL")]
+        ord_url: String,
     },
+
+    /// List the children of a parent inscription, with their media types and sizes. Requires an
+    /// ord server
+    Collection {
+        /// Inscription id of the parent
+        parent: InscriptionId,
+
+        /// Base URL of the ord server used to list the children
+        #[arg(long, env = "ORD_URL")]
+        ord_url: String,
+    },
+
+    /// Exit successfully if a block contains at least one matching inscription, and with an
+    /// error code otherwise. Prints nothing
+    Has {
+        /// Blockhash or block height to check
+        #[arg(long)]
+        block: BlockInd,
+
+        /// Only count inscriptions of these types [text, json, brc20, brc20-valid, html, image,
+        /// metaprotocol=NAME, mime:PATTERN, has-parent, no-parent, parent:ID]
+        #[arg(long)]
+        filter: Vec<Filter>,
+    },
+
+    /// List the inscription filters accepted by `--filter` and what each one matches
+    Filters,
+
+    /// List the image formats this build of ortty can decode and encode
+    Formats,
+
+    /// Print the reveal script of an inscription as an annotated disassembly of its envelopes.
+    /// Requires node with txindex=1
+    Disasm { inscription_id: InscriptionId },
 }
 
+/// Most blocks a single `--from-height`/`--to-height` scan may cover
+const MAX_RANGE_BLOCKS: u64 = 5000;
+
 pub enum ScanMode {
     Block(BlockInd, Vec<Filter>),
+    /// Every block between two heights, inclusive
+    BlockRange(u64, u64, Vec<Filter>),
     Transaction(Txid, Option<BlockInd>, Vec<Filter>),
 }
 
+impl Display for ScanMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanMode::Block(block, _) => write!(f, "block {block}"),
+            ScanMode::BlockRange(from, to, _) => write!(f, "blocks {from}-{to}"),
+            ScanMode::Transaction(txid, _, _) => write!(f, "tx {txid}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum BlockInd {
     BlockHash(BlockHash),
@@ -204,8 +945,195 @@ impl FromStr for BlockInd {
     }
 }
 
+impl Display for BlockInd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockInd::BlockHash(bh) => write!(f, "{bh}"),
+            BlockInd::BlockHeight(height) => write!(f, "{height}"),
+        }
+    }
+}
+
+fn parse_network(s: &str) -> anyhow::Result<Network> {
+    match s.to_lowercase().as_str() {
+        "mainnet" | "main" | "bitcoin" => Ok(Network::Bitcoin),
+        "testnet" | "test" => Ok(Network::Testnet),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        _ => Err(anyhow!("Unknown network")),
+    }
+}
+
+/// Bitcoin Core's default RPC port on a network
+fn rpc_port(network: Network) -> u16 {
+    match network {
+        Network::Testnet => 18332,
+        Network::Signet => 38332,
+        Network::Regtest => 18443,
+        _ => 8332,
+    }
+}
+
+/// Add a port to a host or URL that doesn't have one, such as `localhost` or
+/// `http://node.lan/wallet/w1`. IPv6 addresses need brackets, as in `[::1]`.
+fn with_default_port(host: &str, port: u16) -> String {
+    let (scheme, rest) = match host.find("://") {
+        Some(idx) => host.split_at(idx + 3),
+        None => ("", host),
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let has_port = authority.rsplit_once(':').is_some_and(|(name, port)| {
+        !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) && !name.ends_with(':')
+    });
+    if has_port {
+        host.into()
+    } else {
+        format!("{scheme}{authority}:{port}{path}")
+    }
+}
+
+/// Read sha256 hashes from a file, one per line. Blank lines and lines starting with `#` are
+/// ignored.
+fn read_hash_allowlist(path: &str) -> anyhow::Result<HashSet<String>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut hashes = HashSet::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.len() != 64 || !line.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("Invalid sha256 hash on line {}", lineno + 1);
+        }
+        hashes.insert(line.to_lowercase());
+    }
+    Ok(hashes)
+}
+
+fn parse_hex_width(s: &str) -> anyhow::Result<usize> {
+    match s.parse()? {
+        width @ (8 | 16 | 32) => Ok(width),
+        _ => bail!("Hex width must be 8, 16 or 32"),
+    }
+}
+
+fn parse_ratio(s: &str) -> anyhow::Result<f64> {
+    let ratio: f64 = s.parse()?;
+    if !(0.0..=1.0).contains(&ratio) {
+        bail!("Ratio must be between 0.0 and 1.0");
+    }
+    Ok(ratio)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SortKey {
+    Size,
+    Mime,
+    Kind,
+}
+
+impl SortKey {
+    /// Stable sort, so inscriptions with equal keys keep their block order
+    pub fn sort(&self, inscriptions: &mut [Arc<Inscription>]) {
+        match self {
+            SortKey::Size => inscriptions.sort_by_key(|i| std::cmp::Reverse(i.data.len())),
+            SortKey::Mime => inscriptions.sort_by(|a, b| a.mime.cmp(&b.mime)),
+            SortKey::Kind => inscriptions.sort_by_key(|i| i.parsed.kind()),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = match s.to_lowercase().as_ref() {
+            "size" => Self::Size,
+            "mime" => Self::Mime,
+            "kind" => Self::Kind,
+            _ => return Err(anyhow!("Unknown sort key")),
+        };
+        Ok(key)
+    }
+}
+
+/// A web explorer URL for inscriptions, with `{id}` standing in for the inscription id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerUrl(String);
+
+/// Placeholder replaced by the inscription id in an explorer URL
+const EXPLORER_ID: &str = "{id}";
+
+impl ExplorerUrl {
+    /// The explorer's page for an inscription
+    pub fn url(&self, inscription_id: &str) -> String {
+        self.0.replace(EXPLORER_ID, inscription_id)
+    }
+}
+
+impl Default for ExplorerUrl {
+    fn default() -> Self {
+        ExplorerUrl("https://ordinals.com/inscription/{id}".into())
+    }
+}
+
+impl Display for ExplorerUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for ExplorerUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains(EXPLORER_ID) {
+            bail!("Explorer URL must contain {EXPLORER_ID}, such as https://ordinals.com/inscription/{EXPLORER_ID}");
+        }
+        Ok(ExplorerUrl(s.into()))
+    }
+}
+
+/// How scan results are written to stdout
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Rendered for reading in a terminal
+    #[default]
+    Text,
+    /// One unsigned, Nostr event shaped JSON object per line, for text content only
+    Nostr,
+    /// One JSON array of every inscription, printed at the end
+    Json,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => f.write_str("text"),
+            OutputFormat::Nostr => f.write_str("nostr"),
+            OutputFormat::Json => f.write_str("json"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.to_lowercase().as_ref() {
+            "text" | "terminal" => Self::Text,
+            "nostr" => Self::Nostr,
+            "json" => Self::Json,
+            _ => return Err(anyhow!("Unknown output format")),
+        };
+        Ok(format)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use clap::Parser;
+
     use super::*;
 
     #[test]
@@ -222,6 +1150,90 @@ mod tests {
             BlockInd::from_str(height),
             Ok(BlockInd::BlockHeight(800_000))
         ));
-        assert!(matches!(BlockInd::from_str(nothing), Err(_)));
+        assert!(BlockInd::from_str(nothing).is_err());
+    }
+
+    #[test]
+    fn test_block_range() {
+        let mode = |range: &[&str]| {
+            let args = Args::try_parse_from(["ortty", "scan"].iter().chain(range)).unwrap();
+            args.scan_mode()
+        };
+        assert!(matches!(
+            mode(&["--from-height", "800000", "--to-height", "800010"]),
+            Ok(ScanMode::BlockRange(800000, 800010, _))
+        ));
+        assert!(mode(&["--from-height", "10", "--to-height", "9"]).is_err());
+        assert!(mode(&["--from-height", "0", "--to-height", "5000"]).is_err());
+        assert!(mode(&["--from-height", "0", "--to-height", "4999"]).is_ok());
+        // The whole range of heights is rejected rather than overflowing
+        assert!(mode(&["--from-height", "0", "--to-height", &u64::MAX.to_string()]).is_err());
+        assert!(Args::try_parse_from(["ortty", "scan", "--from-height", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_mempool() {
+        let args = Args::try_parse_from(["ortty", "scan", "--mempool", "--limit", "100"]).unwrap();
+        assert!(args.scans_mempool());
+        assert_eq!(args.mempool_limit(), Some(100));
+        assert!(Args::try_parse_from(["ortty", "scan", "--mempool", "--block", "1"]).is_err());
+        assert!(Args::try_parse_from(["ortty", "scan", "--limit", "100"]).is_err());
+    }
+
+    #[test]
+    fn test_rpc_host() {
+        let host = |args: &[&str]| {
+            let args = [&["ortty"], args, &["doctor"]].concat();
+            Args::try_parse_from(args).unwrap().rpc_host()
+        };
+        assert_eq!(host(&[]), "localhost:8332");
+        assert_eq!(host(&["--network", "mainnet"]), "localhost:8332");
+        assert_eq!(host(&["--network", "testnet"]), "localhost:18332");
+        assert_eq!(host(&["--network", "signet"]), "localhost:38332");
+        assert_eq!(host(&["--network", "regtest"]), "localhost:18443");
+        assert_eq!(
+            host(&["--network", "regtest", "--host", "10.0.0.2"]),
+            "10.0.0.2:18443"
+        );
+        // An explicit port is kept
+        assert_eq!(
+            host(&["--network", "regtest", "--host", "node.lan:9000"]),
+            "node.lan:9000"
+        );
+        assert!(Args::try_parse_from(["ortty", "--network", "litecoin", "doctor"]).is_err());
+
+        assert_eq!(
+            with_default_port("http://node.lan/wallet/w1", 8332),
+            "http://node.lan:8332/wallet/w1"
+        );
+        assert_eq!(
+            with_default_port("http://[::1]:18443", 8332),
+            "http://[::1]:18443"
+        );
+        assert_eq!(with_default_port("[::1]", 8332), "[::1]:8332");
+    }
+
+    #[test]
+    fn test_explorer_url() {
+        let id = "abci0";
+        assert_eq!(
+            ExplorerUrl::default().url(id),
+            "https://ordinals.com/inscription/abci0"
+        );
+        let args = Args::try_parse_from([
+            "ortty",
+            "scan",
+            "--explorer-url",
+            "https://ord.example/inscription/{id}?ref=ortty",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.explorer_url.url(id),
+            "https://ord.example/inscription/abci0?ref=ortty"
+        );
+        assert!(
+            Args::try_parse_from(["ortty", "scan", "--explorer-url", "https://ord.example"])
+                .is_err()
+        );
     }
 }
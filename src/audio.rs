@@ -0,0 +1,128 @@
+use std::{fmt::Display, io::Cursor, time::Duration};
+
+use lofty::{config::ParseOptions, file::AudioFile, probe::Probe};
+
+/// Audio formats that are recognized and summarized instead of printed as binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Mp3,
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Recognize audio by its magic bytes, or failing that by a declared audio mime type
+    pub fn sniff(data: &[u8], mime: &str) -> Option<Self> {
+        if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WAVE" {
+            return Some(AudioFormat::Wav);
+        }
+        if data.starts_with(b"fLaC") {
+            return Some(AudioFormat::Flac);
+        }
+        // An ID3 tag, or the sync bits of an MPEG audio frame
+        if data.starts_with(b"ID3")
+            || (data.len() >= 2 && data[0] == 0xff && data[1] & 0xe0 == 0xe0)
+        {
+            return Some(AudioFormat::Mp3);
+        }
+        let essence = mime.split(';').next().unwrap_or_default().trim();
+        match essence.to_lowercase().as_str() {
+            "audio/mpeg" | "audio/mp3" => Some(AudioFormat::Mp3),
+            "audio/wav" | "audio/wave" | "audio/x-wav" => Some(AudioFormat::Wav),
+            "audio/flac" | "audio/x-flac" => Some(AudioFormat::Flac),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+}
+
+impl Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioFormat::Mp3 => f.write_str("MP3"),
+            AudioFormat::Wav => f.write_str("WAV"),
+            AudioFormat::Flac => f.write_str("FLAC"),
+        }
+    }
+}
+
+/// Properties read from an audio file's headers
+#[derive(Debug, PartialEq, Eq)]
+pub struct AudioSummary {
+    pub duration: Duration,
+    /// Average bitrate, in kbps
+    pub bitrate: Option<u32>,
+}
+
+impl AudioSummary {
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let file = Probe::new(Cursor::new(data))
+            .options(ParseOptions::new().read_tags(false))
+            .guess_file_type()?
+            .read()?;
+        let properties = file.properties();
+        Ok(AudioSummary {
+            duration: properties.duration(),
+            bitrate: properties.audio_bitrate(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::{parse_data, ParsedData};
+
+    /// A mono 16-bit WAV file of `samples` silent samples at 8 kHz
+    fn wav(samples: u32) -> Vec<u8> {
+        let data_len = samples * 2;
+        let mut wav = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data_len).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes()); // PCM
+        wav.extend(1u16.to_le_bytes()); // channels
+        wav.extend(8000u32.to_le_bytes()); // sample rate
+        wav.extend(16000u32.to_le_bytes()); // byte rate
+        wav.extend(2u16.to_le_bytes()); // block align
+        wav.extend(16u16.to_le_bytes()); // bits per sample
+        wav.extend(b"data");
+        wav.extend(data_len.to_le_bytes());
+        wav.resize(wav.len() + data_len as usize, 0);
+        wav
+    }
+
+    #[test]
+    fn test_audio() {
+        let wav = wav(16000);
+        assert_eq!(
+            AudioFormat::sniff(&wav, "application/octet-stream"),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(
+            AudioFormat::sniff(b"fLaC\0\0\0\x22", ""),
+            Some(AudioFormat::Flac)
+        );
+        assert_eq!(
+            AudioFormat::sniff(b"\0\0", "audio/mpeg"),
+            Some(AudioFormat::Mp3)
+        );
+        assert_eq!(AudioFormat::sniff(b"hello", "text/plain"), None);
+
+        let parsed = parse_data(&wav, "audio/wav", &Default::default());
+        assert!(matches!(parsed, ParsedData::Audio(AudioFormat::Wav)));
+        assert_eq!(parsed.detected_format(), Some("wav"));
+
+        let summary = AudioSummary::parse(&wav).unwrap();
+        assert_eq!(summary.duration, Duration::from_secs(2));
+        assert_eq!(summary.bitrate, Some(128));
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+/// A well-formed BRC-20 operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Brc20Op {
+    Deploy {
+        tick: String,
+        max: String,
+        lim: Option<String>,
+    },
+    Mint {
+        tick: String,
+        amt: String,
+    },
+    Transfer {
+        tick: String,
+        amt: String,
+    },
+}
+
+impl Brc20Op {
+    /// Parse a BRC-20 operation, failing with the reason if the JSON does not conform to the
+    /// operation's schema.
+    pub fn parse(json: &Value) -> anyhow::Result<Self> {
+        if json.get("p").and_then(Value::as_str) != Some("brc-20") {
+            bail!("Protocol is not brc-20");
+        }
+        let tick = get_str(json, "tick")?;
+        if !(4..=5).contains(&tick.chars().count()) {
+            bail!("Invalid tick length");
+        }
+        let tick = tick.to_string();
+
+        let op = match get_str(json, "op")? {
+            "deploy" => Brc20Op::Deploy {
+                tick,
+                max: get_amount(json, "max")?,
+                lim: match json.get("lim") {
+                    Some(_) => Some(get_amount(json, "lim")?),
+                    None => None,
+                },
+            },
+            "mint" => Brc20Op::Mint {
+                tick,
+                amt: get_amount(json, "amt")?,
+            },
+            "transfer" => Brc20Op::Transfer {
+                tick,
+                amt: get_amount(json, "amt")?,
+            },
+            op => bail!("Unknown op {op}"),
+        };
+        Ok(op)
+    }
+}
+
+impl std::fmt::Display for Brc20Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Brc20Op::Deploy { tick, max, .. } => write!(f, "deploy {tick} (max {max})"),
+            Brc20Op::Mint { tick, amt } => write!(f, "mint {amt} {tick}"),
+            Brc20Op::Transfer { tick, amt } => write!(f, "transfer {amt} {tick}"),
+        }
+    }
+}
+
+fn get_str<'a>(json: &'a Value, key: &str) -> anyhow::Result<&'a str> {
+    json.get(key)
+        .ok_or_else(|| anyhow!("Missing {key}"))?
+        .as_str()
+        .ok_or_else(|| anyhow!("{key} is not a string"))
+}
+
+/// Amounts are encoded as strings holding a positive decimal number
+fn get_amount(json: &Value, key: &str) -> anyhow::Result<String> {
+    let amount = get_str(json, key)?;
+    let (int, frac) = amount.split_once('.').unwrap_or((amount, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(int) || !is_digits(frac) {
+        bail!("{key} is not a number");
+    }
+    if amount.bytes().all(|b| b == b'0' || b == b'.') {
+        bail!("{key} must be greater than zero");
+    }
+    Ok(amount.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_valid_ops() {
+        let deploy = json!({"p": "brc-20", "op": "deploy", "tick": "ordi", "max": "21000000", "lim": "1000"});
+        assert_eq!(
+            Brc20Op::parse(&deploy).unwrap(),
+            Brc20Op::Deploy {
+                tick: "ordi".into(),
+                max: "21000000".into(),
+                lim: Some("1000".into())
+            }
+        );
+
+        let mint = json!({"p": "brc-20", "op": "mint", "tick": "ordi", "amt": "1000"});
+        assert_eq!(
+            Brc20Op::parse(&mint).unwrap(),
+            Brc20Op::Mint {
+                tick: "ordi".into(),
+                amt: "1000".into()
+            }
+        );
+
+        let transfer = json!({"p": "brc-20", "op": "transfer", "tick": "ordi", "amt": "0.5"});
+        assert!(matches!(
+            Brc20Op::parse(&transfer),
+            Ok(Brc20Op::Transfer { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_ops() {
+        let invalid = [
+            json!({"p": "brc-20", "op": "mint", "tick": "ordi"}),
+            json!({"p": "brc-20", "op": "mint", "tick": "ordi", "amt": 1000}),
+            json!({"p": "brc-20", "op": "mint", "tick": "ordi", "amt": "lots"}),
+            json!({"p": "brc-20", "op": "mint", "tick": "ordi", "amt": "0"}),
+            json!({"p": "brc-20", "op": "burn", "tick": "ordi", "amt": "1"}),
+            json!({"p": "brc-20", "op": "deploy", "tick": "ordi"}),
+            json!({"p": "brc-20", "op": "mint", "tick": "toolong", "amt": "1"}),
+            json!({"p": "sns", "op": "mint", "tick": "ordi", "amt": "1"}),
+        ];
+        for json in invalid {
+            assert!(Brc20Op::parse(&json).is_err(), "{json}");
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use std::{io::Read, time::Duration};
+
+use bitcoin::{consensus::deserialize, Block, BlockHash, Transaction, Txid};
+use bitcoincore_rpc::{
+    jsonrpc::{self, simple_http},
+    Client, RpcApi,
+};
+
+use crate::args::Args;
+
+/// Where blocks and transactions come from: a Bitcoin Core node over RPC, or an Esplora server
+/// when `--esplora` is set
+pub trait ChainSource: Send + Sync {
+    fn get_block_hash(&self, height: u64) -> anyhow::Result<BlockHash>;
+
+    fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block>;
+
+    /// Look up a transaction. The block it was mined in lets a node without `txindex=1` find it
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> anyhow::Result<Transaction>;
+
+    /// Height of the most recent block
+    fn get_block_count(&self) -> anyhow::Result<u64>;
+
+    /// Ids of the transactions waiting in the mempool
+    fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>>;
+}
+
+impl ChainSource for Client {
+    fn get_block_hash(&self, height: u64) -> anyhow::Result<BlockHash> {
+        Ok(RpcApi::get_block_hash(self, height)?)
+    }
+
+    fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block> {
+        Ok(RpcApi::get_block(self, hash)?)
+    }
+
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> anyhow::Result<Transaction> {
+        Ok(RpcApi::get_raw_transaction(self, txid, block_hash)?)
+    }
+
+    fn get_block_count(&self) -> anyhow::Result<u64> {
+        Ok(RpcApi::get_block_count(self)?)
+    }
+
+    fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>> {
+        Ok(RpcApi::get_raw_mempool(self)?)
+    }
+}
+
+/// An Esplora server's REST API, such as `https://blockstream.info/api` or
+/// `https://mempool.space/api`. Transactions are looked up by id alone, so no `txindex` is needed.
+pub struct Esplora {
+    base: String,
+    agent: ureq::Agent,
+}
+
+impl Esplora {
+    pub fn new(base: &str, timeout: Duration) -> Self {
+        Esplora {
+            base: base.trim_end_matches('/').into(),
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    fn get(&self, path: &str) -> anyhow::Result<ureq::Response> {
+        let url = format!("{}{path}", self.base);
+        self.agent
+            .get(&url)
+            .call()
+            .map_err(|e| anyhow::Error::new(e).context(format!("Esplora request to {url} failed")))
+    }
+
+    fn get_text(&self, path: &str) -> anyhow::Result<String> {
+        Ok(self.get(path)?.into_string()?.trim().to_string())
+    }
+
+    fn get_bytes(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.get(path)?.into_reader().read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+impl ChainSource for Esplora {
+    fn get_block_hash(&self, height: u64) -> anyhow::Result<BlockHash> {
+        Ok(self.get_text(&format!("/block-height/{height}"))?.parse()?)
+    }
+
+    fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block> {
+        Ok(deserialize(
+            &self.get_bytes(&format!("/block/{hash}/raw"))?,
+        )?)
+    }
+
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        _block_hash: Option<&BlockHash>,
+    ) -> anyhow::Result<Transaction> {
+        Ok(deserialize(&self.get_bytes(&format!("/tx/{txid}/raw"))?)?)
+    }
+
+    fn get_block_count(&self) -> anyhow::Result<u64> {
+        Ok(self.get_text("/blocks/tip/height")?.parse()?)
+    }
+
+    fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>> {
+        Ok(serde_json::from_reader(
+            self.get("/mempool/txids")?.into_reader(),
+        )?)
+    }
+}
+
+/// Wait before the first retry of a failed request, doubling for each one after it
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Retries requests that failed in a way that may pass, such as a timeout or a node that is
+/// still starting up, up to `retries` times with exponential backoff
+pub struct Retry<S> {
+    inner: S,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl<S: ChainSource> Retry<S> {
+    pub fn new(inner: S, retries: u32) -> Self {
+        Retry {
+            inner,
+            retries,
+            backoff: RETRY_BACKOFF,
+        }
+    }
+
+    fn call<T>(&self, what: &str, f: impl Fn(&S) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let mut delay = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match f(&self.inner) {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.retries && is_retryable(&e) => {
+                    attempt += 1;
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(e) if attempt > 0 => {
+                    return Err(e.context(format!("{what} failed after {} attempts", attempt + 1)))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<S: ChainSource> ChainSource for Retry<S> {
+    fn get_block_hash(&self, height: u64) -> anyhow::Result<BlockHash> {
+        self.call("getblockhash", |inner| inner.get_block_hash(height))
+    }
+
+    fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block> {
+        self.call("getblock", |inner| inner.get_block(hash))
+    }
+
+    fn get_raw_transaction(
+        &self,
+        txid: &Txid,
+        block_hash: Option<&BlockHash>,
+    ) -> anyhow::Result<Transaction> {
+        self.call("getrawtransaction", |inner| {
+            inner.get_raw_transaction(txid, block_hash)
+        })
+    }
+
+    fn get_block_count(&self) -> anyhow::Result<u64> {
+        self.call("getblockcount", |inner| inner.get_block_count())
+    }
+
+    fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>> {
+        self.call("getrawmempool", |inner| inner.get_raw_mempool())
+    }
+}
+
+/// Bitcoin Core's error code while it is loading the block index and not yet answering
+const RPC_IN_WARMUP: i32 = -28;
+
+/// Whether a request might succeed if it is made again. Connection failures, timeouts and
+/// overloaded servers are retried. Errors the server answered with, such as bad credentials, an
+/// unknown method or a missing transaction, are not.
+fn is_retryable(e: &anyhow::Error) -> bool {
+    let retryable_status = |code: u16| code == 429 || code >= 500;
+    e.chain()
+        .find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<bitcoincore_rpc::Error>() {
+                return Some(match e {
+                    bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(e)) => {
+                        match e.downcast_ref::<simple_http::Error>() {
+                            Some(simple_http::Error::HttpErrorCode(code)) => {
+                                retryable_status(*code)
+                            }
+                            _ => true,
+                        }
+                    }
+                    bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)) => {
+                        e.code == RPC_IN_WARMUP
+                    }
+                    bitcoincore_rpc::Error::Io(_) => true,
+                    _ => false,
+                });
+            }
+            match cause.downcast_ref::<ureq::Error>()? {
+                ureq::Error::Status(code, _) => Some(retryable_status(*code)),
+                ureq::Error::Transport(_) => Some(true),
+            }
+        })
+        .unwrap_or(false)
+}
+
+/// Connect to the node, waiting at most `--rpc-timeout` for each request
+pub fn rpc_client(args: &Args) -> anyhow::Result<Client> {
+    let (user, pass) = args.rpc_auth()?.get_user_pass()?;
+    let mut builder = simple_http::Builder::new()
+        .url(&args.rpc_host())?
+        .timeout(Duration::from_secs(args.rpc_timeout));
+    if let Some(user) = user {
+        builder = builder.auth(user, pass);
+    }
+    Ok(Client::from_jsonrpc(jsonrpc::Client::with_transport(
+        builder.build(),
+    )))
+}
+
+/// Connect to the Esplora server set with `--esplora`, or to the node otherwise. Failed requests
+/// are retried up to `--rpc-retries` times.
+pub fn connect(args: &Args) -> anyhow::Result<Box<dyn ChainSource>> {
+    let timeout = Duration::from_secs(args.rpc_timeout);
+    match &args.esplora {
+        Some(base) => Ok(Box::new(Retry::new(
+            Esplora::new(base, timeout),
+            args.rpc_retries,
+        ))),
+        None => Ok(Box::new(Retry::new(rpc_client(args)?, args.rpc_retries))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        io::{BufRead, BufReader, ErrorKind, Write},
+        net::TcpListener,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use anyhow::anyhow;
+
+    use bitcoin::consensus::serialize;
+
+    use super::*;
+    use crate::inscription::tests::inscription_tx;
+
+    /// Serve one HTTP request on a local port with `body`, returning the server's base URL and
+    /// the request line it received
+    pub(crate) fn serve_once(body: Vec<u8>) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            // Skip the headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+            request.trim().to_string()
+        });
+        (base, server)
+    }
+
+    #[test]
+    fn test_esplora_transaction() {
+        let tx = inscription_tx(&[b"hello"]);
+        let (base, server) = serve_once(serialize(&tx));
+        let esplora = Esplora::new(&format!("{base}/api/"), Duration::from_secs(5));
+        assert_eq!(esplora.get_raw_transaction(&tx.txid(), None).unwrap(), tx);
+        assert_eq!(
+            server.join().unwrap(),
+            format!("GET /api/tx/{}/raw HTTP/1.1", tx.txid())
+        );
+
+        let (base, server) = serve_once(b"840000\n".to_vec());
+        let esplora = Esplora::new(&base, Duration::from_secs(5));
+        assert_eq!(esplora.get_block_count().unwrap(), 840000);
+        assert_eq!(server.join().unwrap(), "GET /blocks/tip/height HTTP/1.1");
+    }
+
+    /// Answers `getblockcount` with 840000, after failing the first `failures` times with `error`
+    struct Flaky {
+        failures: u32,
+        error: fn() -> bitcoincore_rpc::Error,
+        calls: AtomicU32,
+    }
+
+    impl ChainSource for Flaky {
+        fn get_block_count(&self) -> anyhow::Result<u64> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)().into());
+            }
+            Ok(840000)
+        }
+
+        fn get_block_hash(&self, _: u64) -> anyhow::Result<BlockHash> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_block(&self, _: &BlockHash) -> anyhow::Result<Block> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_raw_transaction(
+            &self,
+            _: &Txid,
+            _: Option<&BlockHash>,
+        ) -> anyhow::Result<Transaction> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>> {
+            Err(anyhow!("unused"))
+        }
+    }
+
+    fn retry(failures: u32, error: fn() -> bitcoincore_rpc::Error) -> Retry<Flaky> {
+        Retry {
+            inner: Flaky {
+                failures,
+                error,
+                calls: AtomicU32::new(0),
+            },
+            retries: 3,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        let timeout = || bitcoincore_rpc::Error::Io(ErrorKind::TimedOut.into());
+        let flaky = retry(2, timeout);
+        assert_eq!(flaky.get_block_count().unwrap(), 840000);
+        assert_eq!(flaky.inner.calls.load(Ordering::SeqCst), 3);
+
+        // Retries run out
+        let down = retry(10, timeout);
+        let e = down.get_block_count().unwrap_err();
+        assert_eq!(e.to_string(), "getblockcount failed after 4 attempts");
+        assert_eq!(down.inner.calls.load(Ordering::SeqCst), 4);
+
+        // Errors the node answered with fail right away
+        let unknown_method = || {
+            bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+                code: -32601,
+                message: "Method not found".into(),
+                data: None,
+            }))
+        };
+        let unknown = retry(1, unknown_method);
+        assert!(unknown.get_block_count().is_err());
+        assert_eq!(unknown.inner.calls.load(Ordering::SeqCst), 1);
+
+        let unauthorized = || {
+            let e = simple_http::Error::HttpErrorCode(401);
+            bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(Box::new(e)))
+        };
+        let unauthorized = retry(1, unauthorized);
+        assert!(unauthorized.get_block_count().is_err());
+        assert_eq!(unauthorized.inner.calls.load(Ordering::SeqCst), 1);
+    }
+}
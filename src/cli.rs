@@ -0,0 +1,423 @@
+use clap::Parser;
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{stdout, Write},
+    sync::Arc,
+};
+
+use crate::{
+    ansi,
+    args::{self, Args, BlockInd, ScanMode},
+    collection, disasm, doctor,
+    explore::explore,
+    filter, formats, info, inscription,
+    inscription::{Inscription, InscriptionId},
+    output::{format_size, group_digits, Output},
+    scan::{self, BlockSummary, Streamed},
+};
+
+/// Run the command line tool
+pub fn main() -> anyhow::Result<()> {
+    // A missing `.env` in the working directory is not an error
+    dotenv::dotenv().ok();
+
+    let args = Args::parse();
+    let errors_json = args.errors_json;
+    match run(args) {
+        // The reader went away, such as when piping into `head`, so there's nothing left to do
+        Err(e) if is_broken_pipe(&e) => Ok(()),
+        Err(e) if errors_json => {
+            eprintln!("{}", error_json(&e));
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+/// Describe an error for `--errors-json`, with a code naming where in the chain it came from
+fn error_json(e: &anyhow::Error) -> serde_json::Value {
+    let code = e
+        .chain()
+        .find_map(|cause| {
+            if cause.is::<std::io::Error>() {
+                Some("io")
+            } else if cause.is::<bitcoincore_rpc::Error>() {
+                Some("rpc")
+            } else if cause.is::<ureq::Error>() {
+                Some("http")
+            } else if cause.is::<serde_json::Error>() {
+                Some("json")
+            } else {
+                None
+            }
+        })
+        .unwrap_or("other");
+    serde_json::json!({ "error": format!("{e:#}"), "code": code })
+}
+
+fn is_broken_pipe(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
+
+fn run(mut args: Args) -> anyhow::Result<()> {
+    if !args.env_file.is_empty() {
+        for path in &args.env_file {
+            dotenv::from_path(path)
+                .map_err(|e| anyhow::anyhow!("Failed to load {}: {e}", path.display()))?;
+        }
+        // Parse again so that options backed by environment variables see the new values
+        args = Args::parse();
+    }
+
+    match args.command {
+        args::Commands::Scan { .. } => scan(&args)?,
+        args::Commands::Explore { .. } => explore(&args)?,
+        args::Commands::Doctor => doctor::doctor(&args)?,
+        args::Commands::Disasm { ref inscription_id } => disasm::disasm(&args, inscription_id)?,
+        args::Commands::Filters => filter::print_filters(&mut ansi::AnsiStrip::new(
+            std::io::stdout().lock(),
+            args.strip_ansi(),
+        ))?,
+        args::Commands::Formats => formats::print_formats(&mut ansi::AnsiStrip::new(
+            std::io::stdout().lock(),
+            args.strip_ansi(),
+        ))?,
+        args::Commands::Sat {
+            ref sat,
+            ref ord_url,
+        } => {
+            let ids = inscription::sat_inscriptions(ord_url, sat)?;
+            if ids.is_empty() {
+                println!("No inscriptions on sat {sat}");
+            }
+            for id in &ids {
+                inscription::fetch_and_print(&args, id)?;
+            }
+        }
+        args::Commands::Collection {
+            ref parent,
+            ref ord_url,
+        } => collection::collection(&args, parent, ord_url)?,
+        args::Commands::Has { ref block, .. } => {
+            if !scan::block_has_inscription(&args, block)? {
+                std::process::exit(1);
+            }
+        }
+        args::Commands::Info {
+            ref inscription_id,
+            tree,
+            ..
+        } => info::info(&args, inscription_id, tree)?,
+        args::Commands::Inscription {
+            ref inscription_id,
+            ref id_file,
+            ref output_fd,
+            ref ord_url,
+            head_only,
+            ..
+        } => {
+            let mut ids: Vec<_> = inscription_id.iter().cloned().collect();
+            if let Some(path) = id_file {
+                ids.extend(inscription::read_id_file(path)?);
+            }
+            match (output_fd, ord_url) {
+                (_, Some(base)) if head_only => print_heads(&args, base, &ids)?,
+                (Some(fd), _) => write_to_fd(&args, &ids, *fd)?,
+                (None, _) => {
+                    for id in &ids {
+                        inscription::fetch_and_print(&args, id)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the media type and size of each inscription, as reported by the ord server, without
+/// downloading the content
+fn print_heads(args: &Args, base: &str, ids: &[InscriptionId]) -> anyhow::Result<()> {
+    for id in ids {
+        let (mime, size) = inscription::head_from_ord(base, id)?;
+        let size = match size {
+            Some(size) => format_size(size, args.bytes),
+            None => "unknown size".into(),
+        };
+        println!("{id}  {size:>10}  {mime}");
+    }
+    Ok(())
+}
+
+/// Write the raw content of each inscription to an already open file descriptor, reporting
+/// each write on stdout
+fn write_to_fd(args: &Args, ids: &[InscriptionId], fd: u32) -> anyhow::Result<()> {
+    let mut content = OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{fd}"))
+        .map_err(|e| anyhow::anyhow!("File descriptor {fd} is not writable: {e}"))?;
+    for id in ids {
+        for inscription in inscription::fetch(args, id)? {
+            content.write_all(&inscription.data)?;
+            println!(
+                "Wrote {} of {} to fd {fd}",
+                format_size(inscription.data.len(), args.bytes),
+                inscription.inscription_id()
+            );
+        }
+    }
+    content.flush()?;
+    Ok(())
+}
+
+fn scan(args: &Args) -> Result<(), anyhow::Error> {
+    let deadline = scan::Deadline::new(args.deadline());
+    let mut output = Output::new(args)?;
+    emit_scan(args, &deadline, &mut output)?;
+    output.finish()?;
+    if deadline.was_reached() {
+        eprintln!("Deadline reached, results are partial");
+        // Same code as timeout(1), so scripts can tell a partial scan apart from a failure
+        std::process::exit(124);
+    }
+    Ok(())
+}
+
+fn emit_scan(args: &Args, deadline: &scan::Deadline, output: &mut Output) -> anyhow::Result<()> {
+    if args.reads_stdin() {
+        let mut count = 0;
+        scan::scan_stdin(args, deadline, |inscription| {
+            count += 1;
+            output.emit(&inscription)
+        })?;
+        scan::log_scan(args, "stdin", count);
+        return Ok(());
+    }
+    if let Some(path) = args.replay() {
+        let mut count = 0;
+        scan::scan_replay(args, path, deadline, |inscription| {
+            count += 1;
+            output.emit(&inscription)
+        })?;
+        scan::log_scan(args, format!("transcript {}", path.display()), count);
+        return Ok(());
+    }
+    if args.scans_mempool() {
+        let mut count = 0;
+        scan::scan_mempool(args, deadline, |txid, inscriptions| {
+            count += inscriptions.len();
+            output.tx_header(txid)?;
+            for inscription in inscriptions {
+                output.emit(&inscription)?;
+            }
+            Ok(())
+        })?;
+        scan::log_scan(args, "mempool", count);
+        return Ok(());
+    }
+
+    // Scans of several blocks print each block as it is scanned
+    let emit_block = |block: &BlockInd, summary: &BlockSummary, mut inscriptions: Vec<_>| {
+        scan::log_scan(args, format!("block {block}"), inscriptions.len());
+        if let Some(sort) = args.sort() {
+            sort.sort(&mut inscriptions);
+        }
+        output.block_header(summary)?;
+        for inscription in inscriptions {
+            output.emit(&inscription)?;
+        }
+        Ok(())
+    };
+    if let Some(path) = args.blocks() {
+        return scan::scan_block_list(args, path, deadline, emit_block);
+    }
+    if let ScanMode::BlockRange(from, to, filters) = args.scan_mode()? {
+        return scan::scan_block_range(args, from..=to, &filters, deadline, emit_block);
+    }
+
+    if args.count() {
+        let (_, inscriptions) = scan::scan(args, deadline)?;
+        scan::log_scan(args, args.scan_mode()?, inscriptions.len());
+        let mut out = ansi::AnsiStrip::new(stdout().lock(), args.strip_ansi());
+        let count = ScanCount::new(&inscriptions);
+        return print_count(&mut out, &count, args.bytes, args.separators());
+    }
+
+    // Without sorting or paging, a block's inscriptions are printed as they are parsed
+    if let ScanMode::Block(block, filters) = args.scan_mode()? {
+        if args.sort().is_none() && args.page().is_none() && !args.include_transfers() {
+            let count =
+                scan::stream_block(
+                    args,
+                    &block,
+                    &filters,
+                    deadline,
+                    |streamed| match streamed {
+                        Streamed::Block(summary) => output.block_header(summary),
+                        Streamed::Inscription(inscription) => output.emit(&inscription),
+                    },
+                )?;
+            scan::log_scan(args, args.scan_mode()?, count);
+            return Ok(());
+        }
+    }
+
+    let (summary, mut inscriptions) = scan::scan(args, deadline)?;
+    scan::log_scan(args, args.scan_mode()?, inscriptions.len());
+    if let Some(sort) = args.sort() {
+        sort.sort(&mut inscriptions);
+    }
+    if let Some(page) = args.page() {
+        let end = page.end.min(inscriptions.len());
+        let start = page.start.min(end);
+        inscriptions = inscriptions.drain(start..end).collect();
+    }
+    if let Some(summary) = &summary {
+        output.block_header(summary)?;
+    }
+    for inscription in inscriptions {
+        output.emit(&inscription)?;
+    }
+    Ok(())
+}
+
+/// Statistics printed by `scan --count`
+#[derive(Debug, Default, PartialEq)]
+struct ScanCount {
+    total: usize,
+    /// Number of inscriptions of each detected kind
+    kinds: BTreeMap<&'static str, usize>,
+    bytes: usize,
+    /// Id and size of the largest inscription, the first one if several are as large
+    largest: Option<(String, usize)>,
+}
+
+impl ScanCount {
+    fn new(inscriptions: &[Arc<Inscription>]) -> Self {
+        let mut count = ScanCount::default();
+        for inscription in inscriptions {
+            let size = inscription.data.len();
+            count.total += 1;
+            *count.kinds.entry(inscription.parsed.kind()).or_default() += 1;
+            count.bytes += size;
+            if count
+                .largest
+                .as_ref()
+                .is_none_or(|(_, largest)| size > *largest)
+            {
+                count.largest = Some((inscription.inscription_id(), size));
+            }
+        }
+        count
+    }
+}
+
+fn print_count(
+    out: &mut impl Write,
+    count: &ScanCount,
+    raw_bytes: bool,
+    separators: bool,
+) -> anyhow::Result<()> {
+    let number = |n: usize| {
+        if separators {
+            group_digits(n)
+        } else {
+            n.to_string()
+        }
+    };
+    // Sizes in units are never long enough to need separators
+    let size = |bytes: usize| {
+        if raw_bytes {
+            format!("{} bytes", number(bytes))
+        } else {
+            format_size(bytes, false)
+        }
+    };
+    writeln!(out, "Inscriptions: {}", number(count.total))?;
+    for (kind, n) in &count.kinds {
+        writeln!(out, "  {kind}: {}", number(*n))?;
+    }
+    writeln!(out, "Total size: {}", size(count.bytes))?;
+    if let Some((id, largest)) = &count.largest {
+        writeln!(out, "Largest: {id} ({})", size(*largest))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::{tests::fixture_tx, ParseOptions};
+
+    #[test]
+    fn test_scan_count() {
+        let inscriptions: Vec<_> = ["text", "image", "json", "multi"]
+            .into_iter()
+            .flat_map(|name| {
+                Inscription::extract_all(&fixture_tx(name), &ParseOptions::default()).unwrap()
+            })
+            .collect();
+        let count = ScanCount::new(&inscriptions);
+        assert_eq!(count.total, 6);
+        assert_eq!(
+            count.kinds,
+            BTreeMap::from([("html", 1), ("image", 2), ("json", 1), ("text", 2)])
+        );
+        assert_eq!(count.bytes, 320);
+        assert_eq!(count.largest, Some((inscriptions[1].inscription_id(), 120)));
+
+        let mut out = Vec::new();
+        print_count(&mut out, &count, true, true).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "Inscriptions: 6\n  html: 1\n  image: 2\n  json: 1\n  text: 2\n\
+                 Total size: 320 bytes\nLargest: {} (120 bytes)\n",
+                inscriptions[1].inscription_id()
+            )
+        );
+
+        assert_eq!(ScanCount::new(&[]), ScanCount::default());
+
+        let large = ScanCount {
+            total: 12345,
+            kinds: BTreeMap::from([("text", 12345)]),
+            bytes: 1234567,
+            largest: None,
+        };
+        let print = |separators: bool| {
+            let mut out = Vec::new();
+            print_count(&mut out, &large, true, separators).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            print(true),
+            "Inscriptions: 12,345\n  text: 12,345\nTotal size: 1,234,567 bytes\n"
+        );
+        assert_eq!(
+            print(false),
+            "Inscriptions: 12345\n  text: 12345\nTotal size: 1234567 bytes\n"
+        );
+    }
+
+    #[test]
+    fn test_error_json() {
+        let e = anyhow::Error::new(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("Failed to open archive");
+        let json = error_json(&e);
+        assert_eq!(json["code"], "io");
+        assert!(json["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to open archive: "));
+        assert_eq!(
+            error_json(&anyhow::anyhow!("Unknown filter"))["code"],
+            "other"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{stdout, Write};
+
+use anyhow::anyhow;
+use crossterm::style::Stylize;
+use serde_json::Value;
+
+use crate::{ansi::AnsiStrip, args::Args, inscription::InscriptionId, output::format_size};
+
+/// List every child of a parent inscription with its media type and size, paging through an ord
+/// server's children endpoint.
+pub fn collection(args: &Args, parent: &InscriptionId, ord_url: &str) -> anyhow::Result<()> {
+    let base = ord_url.trim_end_matches('/');
+    let mut out = AnsiStrip::new(stdout().lock(), args.strip_ansi());
+    let mut count = 0;
+    for page in 0.. {
+        let (ids, more) =
+            parse_children(&get_json(&format!("{base}/r/children/{parent}/{page}"))?)?;
+        for id in &ids {
+            let (mime, size) = parse_content(&get_json(&format!("{base}/inscription/{id}"))?)?;
+            writeln!(
+                out,
+                "{}  {:>10}  {mime}",
+                id.to_string().yellow(),
+                format_size(size, args.bytes)
+            )?;
+        }
+        count += ids.len();
+        if !more {
+            break;
+        }
+    }
+    if count == 0 {
+        writeln!(out, "No children of {parent}")?;
+    } else {
+        writeln!(out, "{count} children")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn get_json(url: &str) -> anyhow::Result<Value> {
+    let response = ureq::get(url).set("Accept", "application/json").call()?;
+    Ok(serde_json::from_reader(response.into_reader())?)
+}
+
+/// Parse a page of the children endpoint into the child ids, and whether more pages follow
+fn parse_children(page: &Value) -> anyhow::Result<(Vec<InscriptionId>, bool)> {
+    let ids = page
+        .get("ids")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Unexpected children response"))?
+        .iter()
+        .map(|id| {
+            id.as_str()
+                .ok_or_else(|| anyhow!("Unexpected inscription id {id}"))?
+                .parse()
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let more = page.get("more").and_then(Value::as_bool).unwrap_or(false);
+    Ok((ids, more))
+}
+
+/// Media type and content size from an inscription's JSON details
+fn parse_content(inscription: &Value) -> anyhow::Result<(String, usize)> {
+    let mime = inscription
+        .get("content_type")
+        .and_then(Value::as_str)
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let size = inscription
+        .get("content_length")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("Unexpected inscription response"))?;
+    Ok((mime, size as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_children() {
+        let id = "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0";
+        let (ids, more) = parse_children(&json!({"ids": [id], "more": true, "page": 0})).unwrap();
+        assert_eq!(ids.len(), 1);
+        assert_eq!(ids[0].to_string(), id);
+        assert!(more);
+        assert!(parse_children(&json!({"error": "not found"})).is_err());
+
+        let details = json!({"content_type": "image/png", "content_length": 793});
+        assert_eq!(
+            parse_content(&details).unwrap(),
+            ("image/png".to_string(), 793)
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::io::{stdout, Write};
+
+use anyhow::anyhow;
+use bitcoin::{
+    opcodes::all::{OP_ENDIF, OP_IF},
+    script::Instruction,
+    Script,
+};
+use crossterm::style::Stylize;
+
+use crate::{
+    ansi::AnsiStrip,
+    args::Args,
+    chain,
+    inscription::{Inscription, InscriptionId},
+};
+
+/// Pushes longer than this are abbreviated in the listing
+const MAX_PUSH_BYTES: usize = 16;
+
+/// Print the reveal tapscript of an inscription's input as an annotated disassembly.
+pub fn disasm(args: &Args, inscription_id: &InscriptionId) -> anyhow::Result<()> {
+    let client = chain::connect(args)?;
+    let tx = client.get_raw_transaction(&inscription_id.txid(), None)?;
+    let input = Inscription::find(&tx, inscription_id, &args.parse_options())?.input;
+    let tapscript = tx.input[input]
+        .witness
+        .tapscript()
+        .ok_or_else(|| anyhow!("Input has no tapscript"))?;
+
+    let mut out = AnsiStrip::new(stdout().lock(), args.strip_ansi());
+    let lines = annotate(tapscript)?;
+    let width = lines
+        .iter()
+        .map(|(op, _)| op.len())
+        .max()
+        .unwrap_or_default();
+    for (op, note) in lines {
+        match note {
+            Some(note) => writeln!(out, "{op:width$}  {}", format!("; {note}").dark_grey())?,
+            None => writeln!(out, "{op}")?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Where the disassembly is relative to an inscription envelope
+enum Envelope {
+    Outside,
+    /// After the `OP_FALSE` that starts an envelope
+    Started,
+    /// After `OP_FALSE OP_IF`, expecting the `ord` marker
+    Opened,
+    /// Reading tag and value pairs, until the body separator
+    Fields,
+    /// Expecting the value of this tag
+    Value(u8),
+    /// Reading body pushes, until `OP_ENDIF`
+    Body,
+}
+
+/// Disassemble a script into one line per instruction, annotating the parts of any inscription
+/// envelopes
+fn annotate(script: &Script) -> anyhow::Result<Vec<(String, Option<String>)>> {
+    let instructions: Vec<_> = script.instructions().collect::<Result<_, _>>()?;
+    let mut lines = Vec::with_capacity(instructions.len());
+    let mut state = Envelope::Outside;
+    for (idx, instruction) in instructions.iter().enumerate() {
+        let next_is_if = matches!(instructions.get(idx + 1), Some(Instruction::Op(OP_IF)));
+        let (next, note) = match (state, instruction) {
+            (Envelope::Outside, Instruction::PushBytes(push)) if push.is_empty() && next_is_if => {
+                (Envelope::Started, Some("envelope start".to_string()))
+            }
+            (Envelope::Started, Instruction::Op(OP_IF)) => {
+                (Envelope::Opened, Some("envelope opens".to_string()))
+            }
+            (Envelope::Opened, Instruction::PushBytes(push)) if push.as_bytes() == b"ord" => {
+                (Envelope::Fields, Some("ord protocol marker".to_string()))
+            }
+            (Envelope::Fields, Instruction::PushBytes(push)) if push.is_empty() => {
+                (Envelope::Body, Some("body separator".to_string()))
+            }
+            (Envelope::Fields, Instruction::PushBytes(push)) if push.len() == 1 => {
+                let tag = push.as_bytes()[0];
+                (Envelope::Value(tag), Some(format!("{} tag", tag_name(tag))))
+            }
+            (Envelope::Value(tag), Instruction::PushBytes(push)) => {
+                let note = match tag {
+                    1 => format!("content type: {}", String::from_utf8_lossy(push.as_bytes())),
+                    _ => format!("{} value", tag_name(tag)),
+                };
+                (Envelope::Fields, Some(note))
+            }
+            (Envelope::Body, Instruction::PushBytes(push)) => (
+                Envelope::Body,
+                Some(format!("body chunk, {} bytes", push.len())),
+            ),
+            (Envelope::Outside, _) => (Envelope::Outside, None),
+            (_, Instruction::Op(OP_ENDIF)) => (Envelope::Outside, Some("envelope end".into())),
+            (_, _) => (Envelope::Outside, Some("malformed envelope".into())),
+        };
+        state = next;
+        lines.push((format_instruction(instruction), note));
+    }
+    Ok(lines)
+}
+
+/// Name of an envelope tag, as defined by the ord protocol
+fn tag_name(tag: u8) -> String {
+    match tag {
+        1 => "content type".into(),
+        2 => "pointer".into(),
+        3 => "parent".into(),
+        5 => "metadata".into(),
+        7 => "metaprotocol".into(),
+        9 => "content encoding".into(),
+        11 => "delegate".into(),
+        tag => format!("unknown ({tag})"),
+    }
+}
+
+fn format_instruction(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::Op(op) => op.to_string(),
+        Instruction::PushBytes(push) if push.is_empty() => "OP_0".into(),
+        Instruction::PushBytes(push) if push.len() > MAX_PUSH_BYTES => format!(
+            "OP_PUSH {}... ({} bytes)",
+            hex::encode(&push.as_bytes()[..MAX_PUSH_BYTES]),
+            push.len()
+        ),
+        Instruction::PushBytes(push) => format!("OP_PUSH {}", hex::encode(push.as_bytes())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::tests::envelope_script;
+
+    #[test]
+    fn test_annotate() {
+        let script = envelope_script(&[b"hello"]);
+        let lines = annotate(&script).unwrap();
+        let notes: Vec<_> = lines
+            .iter()
+            .map(|(_, note)| note.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                "",
+                "envelope start",
+                "envelope opens",
+                "ord protocol marker",
+                "content type tag",
+                "content type: text/plain",
+                "body separator",
+                "body chunk, 5 bytes",
+                "envelope end",
+            ]
+        );
+        assert_eq!(lines[0].0, "OP_CHECKSIG");
+        assert_eq!(lines[3].0, "OP_PUSH 6f7264");
+    }
+}
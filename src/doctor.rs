@@ -0,0 +1,98 @@
+use std::{
+    fmt::Display,
+    io::{stdout, Write},
+};
+
+use anyhow::bail;
+use bitcoincore_rpc::{Auth, RpcApi};
+use crossterm::style::Stylize;
+
+use crate::{ansi::AnsiStrip, args::Args, chain};
+
+/// Check that the node is reachable and configured the way ortty needs it, printing a checklist.
+pub fn doctor(args: &Args) -> anyhow::Result<()> {
+    let mut out = AnsiStrip::new(stdout(), args.strip_ansi());
+    let mut failed = false;
+
+    match args.rpc_auth() {
+        Ok(auth) => pass(&mut out, "RPC auth", describe_auth(&auth)),
+        Err(e) => {
+            fail(&mut out, "RPC auth", e);
+            bail!("Some checks failed");
+        }
+    }
+
+    let host = args.rpc_host();
+    let client = chain::rpc_client(args)?;
+    let info = match client.get_blockchain_info() {
+        Ok(info) => {
+            pass(&mut out, "RPC connection", &host);
+            info
+        }
+        Err(e) => {
+            fail(&mut out, "RPC connection", format!("{host}: {e}"));
+            bail!("Some checks failed");
+        }
+    };
+    if info.chain == args.network {
+        pass(&mut out, "Network", info.chain);
+    } else {
+        failed = true;
+        fail(
+            &mut out,
+            "Network",
+            format!(
+                "node is on {}, but --network is {}",
+                info.chain, args.network
+            ),
+        );
+    }
+    pass(&mut out, "Block height", info.blocks);
+
+    match client.get_index_info() {
+        Ok(index) => match index.txindex {
+            Some(status) if status.synced => pass(&mut out, "txindex", "enabled"),
+            Some(status) => {
+                failed = true;
+                fail(
+                    &mut out,
+                    "txindex",
+                    format!("still syncing (height {})", status.best_block_height),
+                )
+            }
+            None => {
+                failed = true;
+                fail(
+                    &mut out,
+                    "txindex",
+                    "disabled, required by `inscription` and by `scan --tx` without `--block`",
+                )
+            }
+        },
+        Err(e) => {
+            failed = true;
+            fail(&mut out, "txindex", format!("unable to check: {e}"))
+        }
+    }
+
+    if failed {
+        bail!("Some checks failed");
+    }
+    Ok(())
+}
+
+fn describe_auth(auth: &Auth) -> String {
+    match auth {
+        Auth::None => "none".into(),
+        Auth::UserPass(user, _) => format!("username/password ({user})"),
+        Auth::CookieFile(path) => format!("cookie file ({})", path.display()),
+    }
+}
+
+fn pass(out: &mut impl Write, check: &str, detail: impl Display) {
+    writeln!(out, "{} {check}: {detail}", "✔".green()).ok();
+}
+
+fn fail(out: &mut impl Write, check: &str, detail: impl Display) {
+    writeln!(out, "{} {check}: {detail}", "✘".red()).ok();
+}
@@ -1,13 +1,31 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    io::stdout,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use bitcoincore_rpc::{Client, RpcApi};
 use crossterm::style::Stylize;
-use inquire::{MultiSelect, Select};
+use inquire::{validator::Validation, Confirm, CustomType, MultiSelect, Select};
 
-use crate::{args::Args, filter::Filter, inscription::Inscription};
+use crate::{
+    args::{Args, ExplorerUrl},
+    chain::{self, ChainSource},
+    filter::Filter,
+    inscription::{
+        binding, fetch_from_node, first_inscription, resolve_delegate, Binding, Inscription,
+        ParseOptions, PrintOptions,
+    },
+    output::format_size,
+    scan::scan_block_inscriptions,
+};
 
 mod opts;
 
+/// Number of web pages that can be opened within `WEB_OPEN_WINDOW` before asking for confirmation
+const WEB_OPEN_LIMIT: usize = 5;
+const WEB_OPEN_WINDOW: Duration = Duration::from_secs(60);
+
 use opts::*;
 
 /// Views are maintained in a stack. The top item in the View stack is rendered as the current
@@ -26,51 +44,109 @@ enum View {
     /// This doesn't actually render anything, it is a faux view that retrieve states and pushes
     /// the next view onto the stack
     RetrieveBlockInscriptions(u64),
+    /// Retrieves the inscriptions of the last scanned block again, with the filters used then
+    RepeatLastScan,
+    /// Summarizes the inscriptions in a block before deciding whether to load them
+    PreviewBlock(u64),
     SelectInscriptions(Vec<Arc<Inscription>>, Option<usize>),
-    PrintInscription(Arc<Inscription>),
+    /// Renders the inscription at the index, and allows moving to its neighbours in the list
+    PrintInscription(Vec<Arc<Inscription>>, usize),
 }
 struct State {
     /// The View stack.
     view: Vec<View>,
 
-    /// JSON RPC client.
-    client: Client,
+    /// Node or Esplora server the blocks come from.
+    client: Box<dyn ChainSource>,
 
     /// The user's currently selected filters.
     filters: Vec<Filter>,
 
     // Extra options that the user can set
     extra_opts: ExtraOptions,
+
+    /// How inscription data is classified.
+    parse_opts: ParseOptions,
+
+    /// How inscriptions are printed to the terminal.
+    print_opts: PrintOptions,
+
+    /// Show sizes in raw bytes instead of human-readable units.
+    raw_bytes: bool,
+
+    /// The lowest block height the block browser pages down to.
+    min_height: u64,
+
+    /// The highest block height the block browser starts from, if lower than the tip.
+    max_height: Option<u64>,
+
+    /// Where inscriptions are opened on the web.
+    explorer: ExplorerUrl,
+
+    /// When recent inscriptions were opened on the web, to catch runaway tab opening.
+    web_opens: Vec<Instant>,
+
+    /// The most recent block retrieval in this session, to repeat it from the main menu.
+    last_scan: Option<LastScan>,
+}
+
+/// The parameters of a block retrieval
+#[derive(Clone)]
+struct LastScan {
+    blockheight: u64,
+    filters: Vec<Filter>,
 }
 
 impl State {
     pub fn new(args: &Args) -> anyhow::Result<Self> {
+        let (min_height, max_height) = args.height_bounds()?;
         Ok(State {
             view: vec![View::MainMenu],
-            client: Client::new(&args.rpc_host(), args.rpc_auth()?)?,
-            filters: Filter::all(),
+            client: chain::connect(args)?,
+            filters: match args.filters() {
+                [] => Filter::all(),
+                filters => filters.to_vec(),
+            },
             extra_opts: ExtraOptions::default(),
+            parse_opts: args.parse_options(),
+            print_opts: PrintOptions {
+                raw: false,
+                strip_ansi: false,
+                ..args.print_options()
+            },
+            raw_bytes: args.bytes,
+            min_height,
+            max_height,
+            explorer: args.explorer_url.clone(),
+            web_opens: Vec::new(),
+            last_scan: None,
         })
     }
 }
 
 enum InscriptionView {
     Home,
-    Inscription(Arc<Inscription>),
+    /// An inscription, and whether to show its size in raw bytes
+    Inscription(Arc<Inscription>, bool),
 }
 
 impl std::fmt::Display for InscriptionView {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InscriptionView::Home => f.write_str("Home"),
-            InscriptionView::Inscription(i) => {
+            InscriptionView::Inscription(i, raw_bytes) => {
                 write!(
                     f,
-                    "[{} ({}): {} bytes]",
+                    "[{} ({}): {}]",
                     i.inscription_id().to_string().red(),
                     i.mime.to_string().blue(),
-                    i.data.len().to_string().green()
-                )
+                    format_size(i.data.len(), *raw_bytes).green()
+                )?;
+                if !i.parents.is_empty() {
+                    let parents: Vec<_> = i.parents.iter().map(ToString::to_string).collect();
+                    write!(f, " (child of {})", parents.join(", "))?;
+                }
+                Ok(())
             }
         }
     }
@@ -87,31 +163,45 @@ pub fn explore(args: &Args) -> anyhow::Result<()> {
             } => select_blocks(&mut state, starting_block, index)?,
             View::InscriptionFilters => set_filters(&mut state)?,
             View::ExtraOptions => set_extra_options(&mut state)?,
+            View::PreviewBlock(blockheight) => preview_block(&mut state, blockheight)?,
             View::RetrieveBlockInscriptions(blockheight) => {
-                retrieve_block_inscriptions(&mut state, blockheight)?
+                let filters = state.filters.clone();
+                retrieve_block_inscriptions(&mut state, blockheight, filters)?
             }
+            View::RepeatLastScan => match state.last_scan.clone() {
+                Some(scan) => {
+                    retrieve_block_inscriptions(&mut state, scan.blockheight, scan.filters)?
+                }
+                None => {
+                    state.view.pop();
+                }
+            },
             View::SelectInscriptions(inscriptions, selected) => {
                 select_inscriptions(&mut state, &inscriptions, selected)?
             }
-            View::PrintInscription(inscription) => print_inscription(&mut state, inscription)?,
+            View::PrintInscription(inscriptions, index) => {
+                print_inscription(&mut state, &inscriptions, index)?
+            }
         };
     }
     Ok(())
 }
 
 fn main_menu(state: &mut State) -> anyhow::Result<()> {
-    let options = vec![
-        "View Blocks",
-        "Inscription Filters",
-        "Extra Options",
-        "Quit",
-    ];
+    let repeat = state
+        .last_scan
+        .as_ref()
+        .map(|scan| format!("Repeat Last Scan (block {})", scan.blockheight));
+    let mut options = vec!["View Blocks"];
+    options.extend(repeat.as_deref());
+    options.extend(["Inscription Filters", "Extra Options", "Quit"]);
     let picked = Select::new("Interactive Explorer", options).prompt()?;
     match picked {
         "View Blocks" => state.view.push(View::SelectBlocks {
             starting_block: None,
             index: None,
         }),
+        picked if Some(picked) == repeat.as_deref() => state.view.push(View::RepeatLastScan),
         "Inscription Filters" => state.view.push(View::InscriptionFilters),
         "Extra Options" => state.view.push(View::ExtraOptions),
         "Quit" => state.view.clear(),
@@ -125,20 +215,26 @@ fn select_blocks(
     start: Option<u64>,
     index: Option<usize>,
 ) -> anyhow::Result<()> {
-    let block_number = match start {
-        Some(sb) => sb,
-        None => {
-            let latest_block = state.client.get_blockchain_info()?;
-            latest_block.blocks - 1
-        }
+    let newest_block = {
+        let latest_block = state.client.get_block_count()? - 1;
+        state
+            .max_height
+            .map_or(latest_block, |max| max.min(latest_block))
     };
-    let oldest_block = block_number.checked_sub(100).unwrap_or_default();
+    let block_number = start.unwrap_or(newest_block);
+    let oldest_block = block_number.saturating_sub(100).max(state.min_height);
     let mut options: Vec<_> = (oldest_block..=block_number)
         .map(|i| i.to_string())
         .collect();
 
-    options.push("Previous Page".into());
-    options.push("Next Page".into());
+    // Paging stops at the edges of the allowed heights
+    if block_number < newest_block {
+        options.push("Previous Page".into());
+    }
+    if oldest_block > state.min_height {
+        options.push("Next Page".into());
+    }
+    options.push("Go to height…".into());
     options.push("Home".into());
     options.reverse();
     let picked = Select::new("Select block to view", options)
@@ -162,18 +258,48 @@ fn select_blocks(
                 index: None,
             });
         }
+        "Go to height…" => {
+            // Escape stays on the current page
+            if let Some(height) = prompt_height(state.min_height, newest_block)? {
+                state.view.push(View::SelectBlocks {
+                    starting_block: Some(height),
+                    index: None,
+                });
+            }
+        }
         "Home" => {
             state.view.clear();
             state.view.push(View::MainMenu);
         }
         _ => {
             let picked: u64 = picked.value.parse()?;
-            state.view.push(View::RetrieveBlockInscriptions(picked));
+            if state.extra_opts.preview {
+                state.view.push(View::PreviewBlock(picked));
+            } else {
+                state.view.push(View::RetrieveBlockInscriptions(picked));
+            }
         }
     }
     Ok(())
 }
 
+/// Ask for a block height between `min` and `max`, asking again until one in range is entered.
+/// Returns `None` if the prompt is skipped.
+fn prompt_height(min: u64, max: u64) -> anyhow::Result<Option<u64>> {
+    let height = CustomType::<u64>::new("Go to height:")
+        .with_help_message(&format!("{min} to {max}"))
+        .with_error_message("Enter a block height")
+        .with_validator(move |height: &u64| {
+            Ok(if (min..=max).contains(height) {
+                Validation::Valid
+            } else {
+                Validation::Invalid(format!("Height must be between {min} and {max}").into())
+            })
+        })
+        .prompt_skippable()?;
+    Ok(height)
+}
+
 fn set_filters(state: &mut State) -> anyhow::Result<()> {
     let options = Filter::all();
     let selected: Vec<usize> = options
@@ -205,16 +331,57 @@ fn set_extra_options(state: &mut State) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn retrieve_block_inscriptions(state: &mut State, blockheight: u64) -> anyhow::Result<()> {
+fn preview_block(state: &mut State, blockheight: u64) -> anyhow::Result<()> {
     let bh = state.client.get_block_hash(blockheight)?;
     let block = state.client.get_block(&bh)?;
-    let mut inscriptions = Vec::with_capacity(300);
-    for tx in block.txdata {
-        let txins = Inscription::extract_all(&tx)?
-            .into_iter()
-            .filter(|i| state.filters.iter().any(|f| f.inscription(i)));
-        inscriptions.extend(txins);
+    let mut mimes: Vec<(String, usize)> = Vec::new();
+    for mime in block.txdata.iter().flat_map(Inscription::mimes) {
+        match mimes.iter_mut().find(|(m, _)| *m == mime) {
+            Some((_, count)) => *count += 1,
+            None => mimes.push((mime, 1)),
+        }
     }
+    let total: usize = mimes.iter().map(|(_, count)| count).sum();
+    println!(
+        "Block {blockheight}: {} inscriptions",
+        total.to_string().green()
+    );
+    // Stable sort, so ties keep the order they first appeared in
+    mimes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    if let Some((mime, count)) = mimes.first() {
+        println!("Most common: {} ({count})", mime.as_str().blue());
+    }
+
+    state.view.pop();
+    if total == 0 {
+        return Ok(());
+    }
+    let options = vec!["Load inscriptions", "Back to blocks"];
+    if Select::new("Load this block?", options).prompt()? == "Load inscriptions" {
+        state
+            .view
+            .push(View::RetrieveBlockInscriptions(blockheight));
+    }
+    Ok(())
+}
+
+fn retrieve_block_inscriptions(
+    state: &mut State,
+    blockheight: u64,
+    filters: Vec<Filter>,
+) -> anyhow::Result<()> {
+    let bh = state.client.get_block_hash(blockheight)?;
+    // Unlike the CLI, deselecting every filter in the explorer matches nothing
+    let inscriptions = if filters.is_empty() {
+        Vec::new()
+    } else {
+        scan_block_inscriptions(state.client.as_ref(), &bh, &filters, &state.parse_opts)?
+            .inscriptions
+    };
+    state.last_scan = Some(LastScan {
+        blockheight,
+        filters,
+    });
     state.view.pop();
     if inscriptions.is_empty() {
         println!("No results found");
@@ -237,7 +404,7 @@ fn select_inscriptions(
             inscriptions
                 .iter()
                 .cloned()
-                .map(InscriptionView::Inscription),
+                .map(|i| InscriptionView::Inscription(i, state.raw_bytes)),
         )
         .collect();
     let selected = Select::new("Select inscription", iviews)
@@ -254,34 +421,104 @@ fn select_inscriptions(
             state.view.clear();
             state.view.push(View::MainMenu);
         }
-        InscriptionView::Inscription(i) => state.view.push(View::PrintInscription(i)),
+        InscriptionView::Inscription(..) => state.view.push(View::PrintInscription(
+            inscriptions.to_vec(),
+            selected.index - 1,
+        )),
     }
     Ok(())
 }
 
-fn print_inscription(state: &mut State, inscription: Arc<Inscription>) -> anyhow::Result<()> {
-    if state.extra_opts.web {
-        inscription.open_web()?;
+/// Check whether to open another web page. Once more than `WEB_OPEN_LIMIT` pages have been
+/// opened within `WEB_OPEN_WINDOW`, ask before opening more so that quick navigation doesn't
+/// flood the browser with tabs.
+fn confirm_web(state: &mut State) -> anyhow::Result<bool> {
+    let now = Instant::now();
+    state
+        .web_opens
+        .retain(|opened| now.duration_since(*opened) < WEB_OPEN_WINDOW);
+    if state.web_opens.len() >= WEB_OPEN_LIMIT {
+        let message = format!(
+            "{} pages opened in the last minute. Open another?",
+            state.web_opens.len()
+        );
+        if !Confirm::new(&message).with_default(false).prompt()? {
+            return Ok(false);
+        }
+        state.web_opens.clear();
+    }
+    state.web_opens.push(now);
+    Ok(true)
+}
+
+fn print_inscription(
+    state: &mut State,
+    inscriptions: &[Arc<Inscription>],
+    index: usize,
+) -> anyhow::Result<()> {
+    let inscription = &inscriptions[index];
+    if state.extra_opts.web && confirm_web(state)? {
+        inscription.open_web(&state.explorer)?;
     }
 
     if state.extra_opts.extract {
-        let fname = format!(
-            "{}.{}",
-            inscription.inscription_id(),
-            inscription.file_extension()
-        );
+        let fname = inscription.file_name();
         let p = PathBuf::from(&fname);
         println!("Writing inscription to {}...", fname.green());
-        inscription.write_to_file(&p)?;
+        inscription.write_to_file(&p, false)?;
+    }
+
+    match binding(state.client.as_ref(), inscription) {
+        Binding::Bound => {}
+        Binding::Unbound => println!("{}", "(unbound)".dark_grey()),
+        Binding::Unknown => println!("{}", "(binding unknown)".dark_grey()),
     }
 
     if state.extra_opts.render {
-        inscription.print(false)?;
+        let content = match &inscription.delegate {
+            Some(delegate) => {
+                println!("{}", format!("(delegates to {delegate})").dark_grey());
+                resolve_delegate(inscription.clone(), |id| {
+                    first_inscription(
+                        fetch_from_node(state.client.as_ref(), id, &state.parse_opts),
+                        id,
+                    )
+                })?
+            }
+            None => inscription.clone(),
+        };
+        content.print(&mut stdout(), &state.print_opts)?;
     }
 
     println!();
 
-    state.view.pop();
+    let mut options = Vec::with_capacity(3);
+    if index + 1 < inscriptions.len() {
+        options.push("Next inscription");
+    }
+    if index > 0 {
+        options.push("Previous inscription");
+    }
+    options.push("Back to list");
+    let picked = Select::new("Navigate", options).prompt()?;
+    let index = match picked {
+        "Next inscription" => index + 1,
+        "Previous inscription" => index - 1,
+        "Back to list" => {
+            state.view.pop();
+            return Ok(());
+        }
+        _ => unreachable!(),
+    };
+
+    // Move to the neighbour, and keep the list cursor in sync so that going back to the list
+    // starts on the last viewed inscription
+    if let Some(View::PrintInscription(_, i)) = state.view.last_mut() {
+        *i = index;
+    }
+    if let Some(View::SelectInscriptions(_, cursor)) = state.view.iter_mut().rev().nth(1) {
+        *cursor = Some(index + 1);
+    }
     Ok(())
 }
 
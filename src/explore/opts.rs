@@ -3,12 +3,13 @@ pub(super) enum ExtraOption {
     Render,
     Extract,
     Web,
+    Preview,
 }
 
 impl ExtraOption {
     pub(super) fn all() -> Vec<Self> {
         use ExtraOption::*;
-        vec![Render, Extract, Web]
+        vec![Render, Extract, Web, Preview]
     }
 }
 
@@ -21,6 +22,7 @@ impl std::fmt::Display for ExtraOption {
                 ExtraOption::Render => "Print inscription to terminal",
                 ExtraOption::Extract => "Extract inscriptions to current directory",
                 ExtraOption::Web => "Open inscription on web",
+                ExtraOption::Preview => "Preview blocks before loading their inscriptions",
             }
         )
     }
@@ -30,6 +32,7 @@ pub(super) struct ExtraOptions {
     pub(super) render: bool,
     pub(super) extract: bool,
     pub(super) web: bool,
+    pub(super) preview: bool,
 }
 
 impl ExtraOptions {
@@ -38,6 +41,7 @@ impl ExtraOptions {
             ExtraOption::Render => self.render,
             ExtraOption::Extract => self.extract,
             ExtraOption::Web => self.web,
+            ExtraOption::Preview => self.preview,
         }
     }
 
@@ -53,6 +57,7 @@ impl ExtraOptions {
         self.render = false;
         self.extract = false;
         self.web = false;
+        self.preview = false;
     }
 
     pub(super) fn set_opts(&mut self, opts: &[ExtraOption]) {
@@ -62,6 +67,7 @@ impl ExtraOptions {
                 ExtraOption::Render => self.render = true,
                 ExtraOption::Extract => self.extract = true,
                 ExtraOption::Web => self.web = true,
+                ExtraOption::Preview => self.preview = true,
             }
         }
     }
@@ -73,6 +79,7 @@ impl Default for ExtraOptions {
             render: true,
             extract: Default::default(),
             web: Default::default(),
+            preview: Default::default(),
         }
     }
 }
@@ -1,48 +1,174 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, io::Write, str::FromStr};
 
 use anyhow::anyhow;
+use bitcoin::{hashes::Hash, Txid};
+use crossterm::style::Stylize;
 
-use crate::inscription::Inscription;
+use crate::inscription::{Inscription, InscriptionId};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Filter {
     Text,
     Json,
     Brc20,
+    Brc20Valid,
     Html,
     Image,
+    /// Inscriptions declaring this metaprotocol in their envelope
+    Metaprotocol(String),
+    /// Inscriptions whose mime type matches this exact type or glob, such as `image/*`
+    Mime(String),
+    /// Inscriptions declaring at least one parent, which makes them part of a collection
+    HasParent,
+    /// Inscriptions declaring no parent
+    NoParent,
+    /// Inscriptions declaring this inscription among their parents
+    Parent(InscriptionId),
+    /// Inscriptions not matching the inner filter
+    Not(Box<Filter>),
 }
 
+/// Prefix of the `--filter` token for metaprotocol filters, such as `metaprotocol=sns`
+const METAPROTOCOL_PREFIX: &str = "metaprotocol=";
+
+/// Prefix of the `--filter` token for mime type filters, such as `mime:image/*`
+const MIME_PREFIX: &str = "mime:";
+
+/// Prefix of the `--filter` token for children of a parent, such as `parent:<ID>`
+const PARENT_PREFIX: &str = "parent:";
+
+/// Prefixes of negated filters, such as `!image` or `not:brc20`
+const NOT_PREFIXES: [&str; 2] = ["!", "not:"];
+
 impl Filter {
+    /// Every content filter that doesn't take a value. These are the explorer's choices.
     pub fn all() -> Vec<Self> {
         vec![
             Filter::Text,
             Filter::Json,
             Filter::Brc20,
+            Filter::Brc20Valid,
             Filter::Html,
             Filter::Image,
         ]
     }
 
+    /// Filters on an inscription's parents that don't take a value
+    pub fn provenance() -> Vec<Self> {
+        vec![Filter::HasParent, Filter::NoParent]
+    }
+
+    /// Check an inscription against a set of filters. If any positive filters are specified, the
+    /// inscription must match at least one of them, and otherwise it automatically matches.
+    /// Negated filters are applied afterward, so an inscription matching any of them is dropped.
+    pub fn matches(filters: &[Filter], inscription: &Inscription) -> bool {
+        let (negated, positive): (Vec<_>, Vec<_>) =
+            filters.iter().partition(|f| matches!(f, Filter::Not(_)));
+        (positive.is_empty() || positive.iter().any(|f| f.inscription(inscription)))
+            && negated.iter().all(|f| f.inscription(inscription))
+    }
+
     pub fn inscription(&self, inscription: &Inscription) -> bool {
         match self {
             Filter::Text => inscription.parsed.is_text(),
             Filter::Json => inscription.parsed.is_json(),
             Filter::Brc20 => inscription.parsed.is_brc20(),
+            Filter::Brc20Valid => inscription.parsed.brc20_op().is_some(),
             Filter::Html => inscription.parsed.is_html(),
-            Filter::Image => inscription.parsed.is_image(),
+            Filter::Image => inscription.parsed.is_image() || inscription.parsed.is_svg(),
+            Filter::Metaprotocol(name) => inscription.metaprotocol.as_ref() == Some(name),
+            Filter::Mime(pattern) => mime_matches(pattern, &inscription.mime),
+            Filter::HasParent => !inscription.parents.is_empty(),
+            Filter::NoParent => inscription.parents.is_empty(),
+            Filter::Parent(id) => inscription.parents.contains(id),
+            Filter::Not(filter) => !filter.inscription(inscription),
+        }
+    }
+
+    /// Tokens accepted by `--filter` for this filter. The first is the preferred spelling.
+    pub fn tokens(&self) -> &'static [&'static str] {
+        match self {
+            Filter::Text => &["text"],
+            Filter::Json => &["json"],
+            Filter::Brc20 => &["brc20", "brc-20"],
+            Filter::Brc20Valid => &["brc20-valid", "brc-20-valid"],
+            Filter::Html => &["html"],
+            Filter::Image => &["image"],
+            Filter::Metaprotocol(_) => &["metaprotocol=<NAME>"],
+            Filter::Mime(_) => &["mime:<PATTERN>"],
+            Filter::HasParent => &["has-parent"],
+            Filter::NoParent => &["no-parent"],
+            Filter::Parent(_) => &["parent:<ID>"],
+            Filter::Not(_) => &["!<FILTER>", "not:<FILTER>"],
+        }
+    }
+
+    /// What the filter matches
+    pub fn description(&self) -> &'static str {
+        match self {
+            Filter::Text => "Any text content, including JSON and HTML",
+            Filter::Json => "Content that parses as JSON",
+            Filter::Brc20 => r#"JSON with a "p" field of "brc-20""#,
+            Filter::Brc20Valid => "Well-formed BRC-20 deploy, mint and transfer operations",
+            Filter::Html => "Text inscribed with an HTML media type",
+            Filter::Image => "Content that decodes as an image, whatever its media type",
+            Filter::Metaprotocol(_) => "Inscriptions declaring the metaprotocol NAME",
+            Filter::Mime(_) => "Media types matching PATTERN, exactly or as a glob like image/*",
+            Filter::HasParent => "Children of any parent, such as members of a collection",
+            Filter::NoParent => "Inscriptions without a parent",
+            Filter::Parent(_) => "Children of the inscription ID",
+            Filter::Not(_) => "Excludes what FILTER matches, even if another filter matches it",
         }
     }
 }
 
+/// Print every filter with the tokens `--filter` accepts for it and what it matches
+pub fn print_filters(out: &mut impl Write) -> anyhow::Result<()> {
+    let mut filters = Filter::all();
+    filters.push(Filter::Metaprotocol("NAME".into()));
+    filters.push(Filter::Mime("PATTERN".into()));
+    filters.extend(Filter::provenance());
+    filters.push(Filter::Parent(format!("{}i0", Txid::all_zeros()).parse()?));
+    filters.push(Filter::Not(Box::new(Filter::Image)));
+    let width = filters
+        .iter()
+        .map(|filter| filter.tokens().join(", ").len())
+        .max()
+        .unwrap_or_default();
+    for filter in filters {
+        let tokens = filter.tokens().join(", ");
+        // Negation wraps any filter, so it is named generically rather than after its example
+        let name = match filter {
+            Filter::Not(_) => "Not FILTER".to_string(),
+            Filter::Parent(_) => "Parent ID".to_string(),
+            _ => filter.to_string(),
+        };
+        writeln!(
+            out,
+            "{}  {name}: {}",
+            format!("{tokens:width$}").cyan().bold(),
+            filter.description()
+        )?;
+    }
+    Ok(())
+}
+
 impl Display for Filter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Filter::Text => f.write_str("Text")?,
             Filter::Json => f.write_str("JSON")?,
             Filter::Brc20 => f.write_str("BRC-20")?,
+            Filter::Brc20Valid => f.write_str("BRC-20 (valid)")?,
             Filter::Html => f.write_str("HTML")?,
             Filter::Image => f.write_str("Image")?,
+            Filter::Metaprotocol(name) => write!(f, "Metaprotocol {name}")?,
+            // Written as the token, so it parses back to the same filter
+            Filter::Mime(pattern) => write!(f, "{MIME_PREFIX}{pattern}")?,
+            Filter::HasParent => f.write_str("Has parent")?,
+            Filter::NoParent => f.write_str("No parent")?,
+            Filter::Parent(id) => write!(f, "{PARENT_PREFIX}{id}")?,
+            Filter::Not(filter) => write!(f, "Not {filter}")?,
         }
 
         Ok(())
@@ -53,14 +179,217 @@ impl FromStr for Filter {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let filter = match s.to_lowercase().as_ref() {
-            "text" => Self::Text,
-            "json" => Self::Json,
-            "brc20" | "brc-20" => Self::Brc20,
-            "html" => Self::Html,
-            "image" => Self::Image,
-            _ => return Err(anyhow!("Unknown filter type")),
-        };
-        Ok(filter)
+        if let Some(inner) = NOT_PREFIXES
+            .iter()
+            .find_map(|prefix| strip_prefix(s, prefix))
+        {
+            return match inner.parse()? {
+                Filter::Not(_) => Err(anyhow!("Filters can't be negated twice")),
+                filter => Ok(Filter::Not(Box::new(filter))),
+            };
+        }
+        // Metaprotocol names are kept as given, since they are compared exactly
+        if let Some(name) = strip_prefix(s, METAPROTOCOL_PREFIX) {
+            if name.is_empty() {
+                return Err(anyhow!("Missing metaprotocol name"));
+            }
+            return Ok(Filter::Metaprotocol(name.into()));
+        }
+        if let Some(pattern) = strip_prefix(s, MIME_PREFIX) {
+            if pattern.is_empty() {
+                return Err(anyhow!("Missing mime type"));
+            }
+            return Ok(Filter::Mime(pattern.into()));
+        }
+        if let Some(id) = strip_prefix(s, PARENT_PREFIX) {
+            return Ok(Filter::Parent(id.parse()?));
+        }
+        let s = s.to_lowercase();
+        Filter::all()
+            .into_iter()
+            .chain(Filter::provenance())
+            .find(|filter| filter.tokens().contains(&s.as_str()))
+            .ok_or_else(|| anyhow!("Unknown filter type"))
+    }
+}
+
+/// The rest of `s` after a case insensitive prefix
+fn strip_prefix<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    s.get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &s[prefix.len()..])
+}
+
+/// Match a mime type against a glob pattern, case insensitively. `*` matches any run of
+/// characters and `?` matches a single character, so `image/*` matches every image type.
+pub fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let mime: Vec<char> = mime.to_lowercase().chars().collect();
+
+    // Iterative wildcard matching, backtracking to the most recent `*` on a mismatch
+    let (mut p, mut m) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while m < mime.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, m));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == mime[m] => {
+                p += 1;
+                m += 1;
+            }
+            _ => match star {
+                Some((sp, sm)) => {
+                    p = sp + 1;
+                    m = sm + 1;
+                    star = Some((sp, sm + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::opcodes::{all::OP_ENDIF, all::OP_IF, OP_FALSE};
+    use image::{DynamicImage, ImageFormat};
+
+    use super::*;
+    use crate::inscription::{
+        tests::{inscription_tx, script_tx},
+        ParseOptions, ParsedData,
+    };
+
+    #[test]
+    fn test_filter_tokens() {
+        for filter in Filter::all().into_iter().chain(Filter::provenance()) {
+            for token in filter.tokens() {
+                assert_eq!(token.parse::<Filter>().unwrap(), filter);
+            }
+        }
+        assert_eq!("BRC-20".parse::<Filter>().unwrap(), Filter::Brc20);
+        assert!("video".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn test_metaprotocol() {
+        let filter: Filter = "metaprotocol=sns".parse().unwrap();
+        assert_eq!(filter, Filter::Metaprotocol("sns".into()));
+        assert!("metaprotocol=".parse::<Filter>().is_err());
+
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([7])
+            .push_slice(b"sns")
+            .push_slice([])
+            .push_slice(b"satoshi.sats")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let opts = ParseOptions::default();
+        let sns = &Inscription::extract_all(&script_tx(&script), &opts).unwrap()[0];
+        assert_eq!(sns.metaprotocol.as_deref(), Some("sns"));
+        assert!(filter.inscription(sns));
+
+        let plain = &Inscription::extract_all(&inscription_tx(&[b"hello"]), &opts).unwrap()[0];
+        assert!(!filter.inscription(plain));
+        assert!(!Filter::Metaprotocol("brc-721".into()).inscription(sns));
+    }
+
+    #[test]
+    fn test_mime_filter() {
+        let filter: Filter = "mime:image/*".parse().unwrap();
+        assert_eq!(filter, Filter::Mime("image/*".into()));
+        assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+        assert_eq!(
+            "MIME:image/png".parse::<Filter>().unwrap().to_string(),
+            "mime:image/png"
+        );
+        assert!("mime:".parse::<Filter>().is_err());
+
+        let mut png =
+            Inscription::extract_all(&inscription_tx(&[b"hello"]), &ParseOptions::default())
+                .unwrap()[0]
+                .as_ref()
+                .clone();
+        png.mime = "image/png".into();
+        assert!(Filter::Mime("image/png".into()).inscription(&png));
+        assert!(filter.inscription(&png));
+        assert!(!Filter::Mime("text/*".into()).inscription(&png));
+    }
+
+    #[test]
+    fn test_parent_filters() {
+        let id = "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0";
+        let filter: Filter = format!("parent:{id}").parse().unwrap();
+        assert_eq!(filter, Filter::Parent(id.parse().unwrap()));
+        assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+        assert!("parent:".parse::<Filter>().is_err());
+        assert!("parent:nonsense".parse::<Filter>().is_err());
+        assert!(!Filter::all().contains(&Filter::HasParent));
+
+        let opts = ParseOptions::default();
+        let orphan = &Inscription::extract_all(&inscription_tx(&[b"hello"]), &opts).unwrap()[0];
+        let mut child = orphan.as_ref().clone();
+        child.parents = vec![id.parse().unwrap()];
+        let mut other = orphan.as_ref().clone();
+        other.parents = vec![format!("{}i1", &id[..64]).parse().unwrap()];
+
+        assert!(Filter::HasParent.inscription(&child));
+        assert!(!Filter::HasParent.inscription(orphan));
+        assert!(Filter::NoParent.inscription(orphan));
+        assert!(filter.inscription(&child));
+        assert!(!filter.inscription(&other));
+        assert!(!filter.inscription(orphan));
+    }
+
+    #[test]
+    fn test_negated_filter() {
+        let not_image = Filter::Not(Box::new(Filter::Image));
+        assert_eq!("!image".parse::<Filter>().unwrap(), not_image);
+        assert_eq!("NOT:image".parse::<Filter>().unwrap(), not_image);
+        assert_eq!(
+            "!mime:image/*".parse::<Filter>().unwrap(),
+            Filter::Not(Box::new(Filter::Mime("image/*".into())))
+        );
+        assert!("!!image".parse::<Filter>().is_err());
+        assert!("!video".parse::<Filter>().is_err());
+
+        let opts = ParseOptions::default();
+        let text = &Inscription::extract_all(&inscription_tx(&[b"hello"]), &opts).unwrap()[0];
+        let mut image = text.as_ref().clone();
+        image.parsed = ParsedData::Image(DynamicImage::new_rgb8(1, 1), ImageFormat::Png);
+        let brc20 = br#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1"}"#;
+        let brc20 = &Inscription::extract_all(&inscription_tx(&[brc20]), &opts).unwrap()[0];
+
+        // Negated filters alone keep everything else
+        let filters = std::slice::from_ref(&not_image);
+        assert!(Filter::matches(filters, text));
+        assert!(!Filter::matches(filters, &image));
+
+        // Negated filters are applied after positive ones, whatever their order
+        let filters = ["!brc20".parse().unwrap(), Filter::Text];
+        assert!(Filter::matches(&filters, text));
+        assert!(!Filter::matches(&filters, brc20));
+        assert!(!Filter::matches(&filters, &image));
+    }
+
+    #[test]
+    fn test_mime_matches() {
+        assert!(mime_matches("image/png", "image/png"));
+        assert!(mime_matches("image/*", "image/webp"));
+        assert!(mime_matches("IMAGE/*", "image/PNG"));
+        assert!(mime_matches("text/plain*", "text/plain;charset=utf-8"));
+        assert!(mime_matches("*/*", "model/gltf-binary"));
+        assert!(mime_matches("image/?ng", "image/png"));
+        assert!(!mime_matches("image/*", "text/plain"));
+        assert!(!mime_matches("image/png", "image/pngx"));
+        assert!(!mime_matches("application/*", "image/png"));
     }
 }
@@ -0,0 +1,90 @@
+use std::io::{Cursor, Write};
+
+use crossterm::style::Stylize;
+use image::{
+    error::{ImageFormatHint, UnsupportedErrorKind},
+    DynamicImage, ImageError, ImageFormat,
+};
+
+/// Every image format the `image` crate knows about, whether or not this build supports it
+const FORMATS: [ImageFormat; 15] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Avif,
+    ImageFormat::Bmp,
+    ImageFormat::Ico,
+    ImageFormat::Tiff,
+    ImageFormat::Tga,
+    ImageFormat::Dds,
+    ImageFormat::Pnm,
+    ImageFormat::Hdr,
+    ImageFormat::OpenExr,
+    ImageFormat::Farbfeld,
+    ImageFormat::Qoi,
+];
+
+/// List the image formats this build can decode and encode. Support depends on the features the
+/// `image` crate was built with, so it is probed rather than listed by hand.
+pub fn print_formats(out: &mut impl Write) -> anyhow::Result<()> {
+    for format in FORMATS {
+        let name = format!("{format:?}").to_lowercase();
+        let support = match (can_decode(format), can_encode(format)) {
+            (true, true) => "decode, encode".green(),
+            (true, false) => "decode".green(),
+            (false, true) => "encode only".yellow(),
+            (false, false) => "not supported".red(),
+        };
+        writeln!(out, "{}  {support}", format!("{name:8}").cyan().bold())?;
+    }
+    // SVG is rasterized separately, see `ParsedData::Svg`
+    writeln!(
+        out,
+        "{}  {}",
+        format!("{:8}", "svg").cyan().bold(),
+        "decode".green()
+    )?;
+    Ok(())
+}
+
+/// Whether an error means the format was left out of the build, as opposed to the data being bad
+fn is_missing_format(e: &ImageError, format: ImageFormat) -> bool {
+    matches!(
+        e,
+        ImageError::Unsupported(e)
+            if matches!(e.kind(), UnsupportedErrorKind::Format(ImageFormatHint::Exact(f)) if f == format)
+    )
+}
+
+/// Without a decoder for the format, decoding fails before the data is read
+fn can_decode(format: ImageFormat) -> bool {
+    match image::load_from_memory_with_format(&[], format) {
+        Ok(_) => true,
+        Err(e) => !is_missing_format(&e, format),
+    }
+}
+
+fn can_encode(format: ImageFormat) -> bool {
+    let image = DynamicImage::new_rgb8(1, 1);
+    match image.write_to(&mut Cursor::new(Vec::new()), format) {
+        Ok(_) => true,
+        Err(ImageError::Unsupported(e)) => !matches!(e.kind(), UnsupportedErrorKind::Format(_)),
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        assert!(can_decode(ImageFormat::Png));
+        assert!(can_encode(ImageFormat::Png));
+        assert!(can_decode(ImageFormat::WebP));
+        // The image crate has no HDR encoder, and AVIF decoding needs a feature ortty doesn't enable
+        assert!(!can_encode(ImageFormat::Hdr));
+        assert!(!can_decode(ImageFormat::Avif));
+    }
+}
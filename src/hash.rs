@@ -0,0 +1,62 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+use bitcoin::hashes::{sha256, Hash};
+
+/// Algorithms for hashing inscription content, to recognize the same content across
+/// inscriptions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    /// Hash the data, as lowercase hex
+    pub fn digest(&self, data: &[u8]) -> String {
+        match self {
+            HashAlgo::Sha256 => hex::encode(sha256::Hash::hash(data).to_byte_array()),
+            HashAlgo::Blake3 => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+}
+
+impl Display for HashAlgo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgo::Sha256 => f.write_str("sha256"),
+            HashAlgo::Blake3 => f.write_str("blake3"),
+        }
+    }
+}
+
+impl FromStr for HashAlgo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let algo = match s.to_lowercase().as_ref() {
+            "sha256" => Self::Sha256,
+            "blake3" => Self::Blake3,
+            _ => return Err(anyhow!("Unknown hash algorithm")),
+        };
+        Ok(algo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            HashAlgo::Sha256.digest(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            HashAlgo::Blake3.digest(b"abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+}
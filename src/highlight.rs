@@ -0,0 +1,81 @@
+use std::sync::OnceLock;
+
+use syntect::{
+    easy::HighlightLines,
+    highlighting::ThemeSet,
+    parsing::SyntaxSet,
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+
+use crate::theme::Theme;
+
+/// Syntax definitions are slow to load, so they are only loaded once and only when needed
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// File extension of the syntax used for a declared mime type, ignoring any parameters
+fn syntax_extension(mime: &str) -> Option<&'static str> {
+    let essence = mime.split(';').next().unwrap_or_default().trim();
+    let extension = match essence.to_lowercase().as_str() {
+        "text/javascript" | "application/javascript" | "application/x-javascript" => "js",
+        "text/css" => "css",
+        "text/html" => "html",
+        "text/xml" | "application/xml" | "image/svg+xml" => "xml",
+        "text/markdown" => "md",
+        "text/x-python" | "application/x-python" => "py",
+        "text/x-rust" => "rs",
+        "text/x-c" => "c",
+        "text/x-shellscript" | "application/x-sh" => "sh",
+        "application/yaml" | "text/yaml" => "yaml",
+        _ => return None,
+    };
+    Some(extension)
+}
+
+/// Colors from the syntax themes that best match each JSON theme
+fn theme_name(theme: Theme) -> &'static str {
+    match theme {
+        Theme::Default => "base16-ocean.dark",
+        Theme::Monokai => "base16-mocha.dark",
+        Theme::Solarized => "Solarized (dark)",
+    }
+}
+
+/// Highlight text as the code or markup its mime type declares, returning it with ANSI color
+/// codes. Mime types without a known syntax are not highlighted.
+pub fn highlight(text: &str, mime: &str, theme: Theme) -> Option<String> {
+    let syntax = syntaxes().find_syntax_by_extension(syntax_extension(mime)?)?;
+    let mut lines = HighlightLines::new(syntax, &themes().themes[theme_name(theme)]);
+    let mut highlighted = String::new();
+    for line in LinesWithEndings::from(text) {
+        let ranges = lines.highlight_line(line, syntaxes()).ok()?;
+        highlighted.push_str(&as_24_bit_terminal_escaped(&ranges, false));
+    }
+    // Reset the colors, so they don't carry over into whatever is printed next
+    highlighted.push_str("\x1b[0m");
+    Some(highlighted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let js = "const x = 1;\nconsole.log(x);\n";
+        let highlighted = highlight(js, "text/javascript;charset=utf-8", Theme::Default).unwrap();
+        assert!(highlighted.contains("\x1b[38;2;"));
+        assert!(highlighted.contains("console"));
+        for theme in [Theme::Monokai, Theme::Solarized] {
+            assert!(highlight("a { color: red }", "text/css", theme).is_some());
+        }
+        assert!(highlight("plain words", "text/plain", Theme::Default).is_none());
+    }
+}
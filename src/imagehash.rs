@@ -0,0 +1,75 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+use image::{imageops::FilterType, DynamicImage};
+
+/// A 64 bit perceptual difference hash (dHash) of an image. Visually similar images have hashes
+/// that differ in only a few bits, regardless of their size or encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHash(u64);
+
+impl ImageHash {
+    /// Hash an image by shrinking it to 9x8 grayscale pixels and recording whether each pixel
+    /// is brighter than its right neighbour
+    pub fn new(image: &DynamicImage) -> Self {
+        let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+        let mut hash = 0;
+        for y in 0..8 {
+            for x in 0..8 {
+                let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+                hash = (hash << 1) | u64::from(brighter);
+            }
+        }
+        ImageHash(hash)
+    }
+
+    /// Number of bits that differ between two hashes
+    pub fn distance(&self, other: &ImageHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl Display for ImageHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for ImageHash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(anyhow!("Image hash must be 16 hex characters"));
+        }
+        let hash = u64::from_str_radix(s, 16).map_err(|_| anyhow!("Invalid image hash"))?;
+        Ok(ImageHash(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, _| {
+            let v = (255 - x * 255 / width) as u8;
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn test_image_hash() {
+        let hash = ImageHash::new(&gradient(64, 64));
+        let resized = ImageHash::new(&gradient(300, 200));
+        assert!(hash.distance(&resized) <= 2);
+
+        let flipped = ImageHash::new(&gradient(64, 64).fliph());
+        assert!(hash.distance(&flipped) > 32);
+
+        assert_eq!(hash.to_string().parse::<ImageHash>().unwrap(), hash);
+        assert!("xyz".parse::<ImageHash>().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    io::{stdout, Write},
+    sync::Arc,
+};
+
+use anyhow::Context;
+
+use crossterm::style::Stylize;
+
+use crate::{
+    ansi::AnsiStrip,
+    args::Args,
+    inscription::{
+        self, binding, first_inscription, mime_charset, Binding, Inscription, InscriptionId,
+        MAX_DELEGATE_DEPTH,
+    },
+    output::format_size,
+};
+
+/// Print the metadata of each inscription for an inscription id as an aligned table, or with
+/// `tree` set, the tree of its delegate and parent links.
+pub fn info(args: &Args, inscription_id: &InscriptionId, tree: bool) -> anyhow::Result<()> {
+    let mut out = AnsiStrip::new(stdout().lock(), args.strip_ansi());
+    // An ord server doesn't tell us which output the reveal spent
+    let client = match args.ord_url() {
+        Some(_) => None,
+        None => Some(crate::chain::connect(args)?),
+    };
+    for (idx, inscription) in inscription::fetch(args, inscription_id)?.iter().enumerate() {
+        if idx > 0 {
+            writeln!(out)?;
+        }
+        if tree {
+            let mut lookup =
+                |id: &InscriptionId| first_inscription(inscription::fetch(args, id), id);
+            for line in tree_lines(inscription, &mut lookup)? {
+                writeln!(out, "{line}")?;
+            }
+        } else {
+            let binding = client
+                .as_deref()
+                .map_or(Binding::Unknown, |client| binding(client, inscription));
+            print_table(&mut out, &metadata(args, inscription, binding))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The metadata shown for an inscription, as key-value pairs. Values that don't apply to the
+/// inscription are left out.
+fn metadata(
+    args: &Args,
+    inscription: &Inscription,
+    binding: Binding,
+) -> Vec<(&'static str, String)> {
+    let mut rows = vec![
+        ("id", inscription.inscription_id()),
+        ("mime", inscription.mime.clone()),
+        ("size", format_size(inscription.data.len(), args.bytes)),
+        ("kind", inscription.parsed.kind().to_string()),
+        ("encoding", inscription.encoding().to_string()),
+        (
+            "encoded size",
+            format_size(inscription.encoded_size(), args.bytes),
+        ),
+        ("binding", binding.to_string()),
+    ];
+    if let Some(metaprotocol) = &inscription.metaprotocol {
+        rows.push(("metaprotocol", metaprotocol.clone()));
+    }
+    for parent in &inscription.parents {
+        rows.push(("parent", parent.to_string()));
+    }
+    if let Some(pointer) = inscription.pointer {
+        rows.push(("pointer", pointer.to_string()));
+    }
+    if inscription.truncated {
+        rows.push(("truncated", "yes".into()));
+    }
+    if let Some(charset) = mime_charset(&inscription.mime) {
+        rows.push(("charset", charset.to_string()));
+    }
+    if let Some(format) = inscription.parsed.detected_format() {
+        rows.push(("format", format.to_string()));
+    }
+    if let Some(op) = inscription.parsed.brc20_op() {
+        rows.push(("protocol", format!("brc-20 {op}")));
+    }
+    rows
+}
+
+/// The lines of an inscription's tree of delegate and parent links, indented by depth, looking
+/// each linked inscription up with `lookup`. A link back to an inscription higher up its own
+/// branch is marked as a cycle and not followed, and links more than [`MAX_DELEGATE_DEPTH`] deep
+/// are listed without being fetched.
+fn tree_lines(
+    inscription: &Inscription,
+    lookup: &mut impl FnMut(&InscriptionId) -> anyhow::Result<Arc<Inscription>>,
+) -> anyhow::Result<Vec<String>> {
+    let id = inscription.inscription_id();
+    let mut lines = vec![format!("{id}  {}", inscription.mime)];
+    push_links(&mut lines, inscription, &mut vec![id], lookup)?;
+    Ok(lines)
+}
+
+/// Add the links of `inscription` to the tree, below the branch of ids in `path`
+fn push_links(
+    lines: &mut Vec<String>,
+    inscription: &Inscription,
+    path: &mut Vec<String>,
+    lookup: &mut impl FnMut(&InscriptionId) -> anyhow::Result<Arc<Inscription>>,
+) -> anyhow::Result<()> {
+    let links = (inscription.delegate.iter().map(|id| ("delegate", id)))
+        .chain(inscription.parents.iter().map(|id| ("parent", id)));
+    for (role, id) in links {
+        let indent = "  ".repeat(path.len());
+        if path.contains(&id.to_string()) {
+            lines.push(format!("{indent}{role} {id}  (cycle)"));
+            continue;
+        }
+        if path.len() > MAX_DELEGATE_DEPTH {
+            lines.push(format!("{indent}{role} {id}  (too deep to follow)"));
+            continue;
+        }
+        let linked = lookup(id).with_context(|| format!("Failed to look up {role} {id}"))?;
+        lines.push(format!("{indent}{role} {id}  {}", linked.mime));
+        path.push(id.to_string());
+        push_links(lines, &linked, path, lookup)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Print key-value pairs with the values aligned in a single column
+fn print_table(out: &mut impl Write, rows: &[(&str, String)]) -> anyhow::Result<()> {
+    let width = rows
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or_default();
+    for (key, value) in rows {
+        writeln!(out, "{}  {value}", format!("{key:width$}").cyan().bold())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::{tests::inscription_tx, ParseOptions};
+
+    #[test]
+    fn test_tree_lines() {
+        let txid = inscription_tx(&[]).txid();
+        let id = |index: usize| -> InscriptionId { format!("{txid}i{index}").parse().unwrap() };
+        let inscription = |index: usize, delegate: Option<usize>, parents: &[usize]| {
+            let mut inscription =
+                Inscription::extract_all(&inscription_tx(&[b"body"]), &ParseOptions::default())
+                    .unwrap()[0]
+                    .as_ref()
+                    .clone();
+            inscription.txid = txid;
+            inscription.index = index;
+            inscription.mime = format!("text/{index}");
+            inscription.delegate = delegate.map(id);
+            inscription.parents = parents.iter().copied().map(id).collect();
+            Arc::new(inscription)
+        };
+
+        // 0 delegates to 1 and is a child of 2, 1 is a child of 2, and 2 is a child of 0
+        let chain = [
+            inscription(0, Some(1), &[2]),
+            inscription(1, None, &[2]),
+            inscription(2, None, &[0]),
+        ];
+        let mut lookup = |id: &InscriptionId| Ok(chain[id.index()].clone());
+        let lines = tree_lines(&chain[0], &mut lookup).unwrap();
+        assert_eq!(
+            lines,
+            [
+                format!("{}  text/0", id(0)),
+                format!("  delegate {}  text/1", id(1)),
+                format!("    parent {}  text/2", id(2)),
+                format!("      parent {}  (cycle)", id(0)),
+                format!("  parent {}  text/2", id(2)),
+                format!("    parent {}  (cycle)", id(0)),
+            ]
+        );
+
+        // A chain of parents deeper than is followed
+        let deep: Vec<_> = (0..10).map(|i| inscription(i, None, &[i + 1])).collect();
+        let mut lookup = |id: &InscriptionId| Ok(deep[id.index()].clone());
+        let lines = tree_lines(&deep[0], &mut lookup).unwrap();
+        assert_eq!(lines.len(), MAX_DELEGATE_DEPTH + 2);
+        assert!(lines.last().unwrap().ends_with("(too deep to follow)"));
+
+        let mut missing = |_: &InscriptionId| Err(anyhow::anyhow!("No such transaction"));
+        assert!(tree_lines(&chain[1], &mut missing).is_err());
+    }
+
+    #[test]
+    fn test_print_table() {
+        let mut text = Vec::new();
+        let rows = [("id", "abci0".to_string()), ("mime", "text/plain".into())];
+        print_table(&mut AnsiStrip::new(&mut text, true), &rows).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "id    abci0\nmime  text/plain\n"
+        );
+    }
+}
@@ -1,21 +1,63 @@
 use anyhow::anyhow;
-use bitcoincore_rpc::RpcApi;
-use image::{DynamicImage, EncodableLayout, ImageFormat};
-use std::{collections::VecDeque, path::PathBuf, sync::Arc};
+use image::{
+    codecs::gif::GifDecoder,
+    imageops::{self, FilterType},
+    AnimationDecoder, DynamicImage, Frame, ImageFormat, ImageResult, Rgba, RgbaImage,
+};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    io::{stdout, Cursor, Read, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bitcoin::{
+    hashes::Hash,
     opcodes::all::{OP_ENDIF, OP_IF},
     script::Instruction,
+    taproot::{TAPROOT_ANNEX_PREFIX, TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT},
     Script, Transaction, TxIn, Txid,
 };
-use colored_json::{to_colored_json, ColorMode};
+use colored_json::{ColorMode, ColoredFormatter, PrettyFormatter};
+use crossterm::{
+    style::{Color, Stylize},
+    tty::IsTty,
+};
+use encoding_rs::Encoding;
+use flate2::read::GzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use qrcode::{render::unicode::Dense1x2, QrCode};
+use resvg::{tiny_skia, usvg};
+
+use crate::{
+    ansi::AnsiStrip,
+    args::ExplorerUrl,
+    audio::{AudioFormat, AudioSummary},
+    brc20::Brc20Op,
+    chain::ChainSource,
+    highlight::highlight,
+    output::format_size,
+    pdf::{PdfSummary, PDF_MAGIC},
+    recursion::script_refs,
+    theme::Theme,
+};
 
+/// The classification of an inscription's data, along with the decoded content.
 #[derive(Clone)]
 pub enum ParsedData {
+    /// Audio, summarized from its headers rather than printed
+    Audio(AudioFormat),
     Binary,
     Html(String),
-    Image(DynamicImage),
+    /// A decoded image and the format it was decoded from
+    Image(DynamicImage, ImageFormat),
     Json(serde_json::Value),
+    /// A PDF document, recognized by its magic bytes
+    Pdf,
+    /// An SVG document, declared as `image/svg+xml`
+    Svg(String),
     Text(String),
 }
 
@@ -27,6 +69,14 @@ impl ParsedData {
         }
     }
 
+    /// The BRC-20 operation, if this is JSON conforming to one of the BRC-20 operation schemas
+    pub fn brc20_op(&self) -> Option<Brc20Op> {
+        match self {
+            ParsedData::Json(json) => Brc20Op::parse(json).ok(),
+            _ => None,
+        }
+    }
+
     pub fn is_text(&self) -> bool {
         matches!(
             self,
@@ -43,11 +93,57 @@ impl ParsedData {
     }
 
     pub fn is_image(&self) -> bool {
-        matches!(self, ParsedData::Image(_))
+        matches!(self, ParsedData::Image(..))
+    }
+
+    pub fn is_svg(&self) -> bool {
+        matches!(self, ParsedData::Svg(_))
+    }
+
+    /// Short name of the detected kind of data
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParsedData::Audio(_) => "audio",
+            ParsedData::Binary => "binary",
+            ParsedData::Html(_) => "html",
+            ParsedData::Image(..) => "image",
+            ParsedData::Json(_) => "json",
+            ParsedData::Pdf => "pdf",
+            ParsedData::Svg(_) => "svg",
+            ParsedData::Text(_) => "text",
+        }
+    }
+
+    /// Subdirectory for this kind of data when organizing extracted files
+    pub fn extract_dir(&self) -> &'static str {
+        match self {
+            ParsedData::Audio(_) => "audio",
+            ParsedData::Binary => "binary",
+            ParsedData::Html(_) => "html",
+            ParsedData::Image(..) | ParsedData::Svg(_) => "images",
+            ParsedData::Json(_) => "json",
+            ParsedData::Pdf => "documents",
+            ParsedData::Text(_) => "text",
+        }
+    }
+
+    /// The concrete format the data was detected as, such as `png` for a PNG image or `json` for
+    /// JSON. Binary data has no detected format.
+    pub fn detected_format(&self) -> Option<&str> {
+        match self {
+            ParsedData::Audio(format) => Some(format.extension()),
+            ParsedData::Binary => None,
+            ParsedData::Html(_) => Some("html"),
+            ParsedData::Image(_, format) => format.extensions_str().first().copied(),
+            ParsedData::Json(_) => Some("json"),
+            ParsedData::Pdf => Some("pdf"),
+            ParsedData::Svg(_) => Some("svg"),
+            ParsedData::Text(_) => Some("text"),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct InscriptionId(Txid, usize);
 
 impl std::str::FromStr for InscriptionId {
@@ -67,19 +163,209 @@ impl std::str::FromStr for InscriptionId {
     }
 }
 
+impl InscriptionId {
+    /// The transaction that revealed the inscription
+    pub fn txid(&self) -> Txid {
+        self.0
+    }
+
+    /// Index of the inscription among those revealed by the transaction, counting across every
+    /// input. It is not the index of the input that holds it.
+    pub fn index(&self) -> usize {
+        self.1
+    }
+
+    /// Decode the binary form ord uses for ids inside envelopes, such as in parent fields: the
+    /// 32 txid bytes, followed by the index as little endian bytes with trailing zeros left out
+    fn from_envelope_value(value: &[u8]) -> Option<Self> {
+        if !(32..=36).contains(&value.len()) {
+            return None;
+        }
+        let (txid, index) = value.split_at(32);
+        let txid = Txid::from_slice(txid).ok()?;
+        let mut index_bytes = [0; 4];
+        index_bytes[..index.len()].copy_from_slice(index);
+        Some(InscriptionId(
+            txid,
+            u32::from_le_bytes(index_bytes) as usize,
+        ))
+    }
+
+    /// Parse a single line of an id file. A line is either a plain inscription id
+    /// (`<txid>i0`) or a range of inscriptions in the same transaction (`<txid>i0-3`), which is
+    /// expanded into every index of the range, inclusive. Like any id, the indices count
+    /// inscriptions across the transaction's inputs.
+    pub fn parse_line(line: &str) -> anyhow::Result<Vec<InscriptionId>> {
+        let (txid, range) = line
+            .split_once('i')
+            .ok_or_else(|| anyhow!("Inscription ID parse error"))?;
+        let txid: Txid = txid
+            .parse()
+            .map_err(|_| anyhow!("Inscription ID parse error"))?;
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.parse::<usize>()?, end.parse::<usize>()?),
+            None => {
+                let index = range.parse::<usize>()?;
+                (index, index)
+            }
+        };
+        if start > end {
+            return Err(anyhow!("Invalid inscription range {start}-{end}"));
+        }
+        if end - start >= MAX_ID_RANGE {
+            return Err(anyhow!(
+                "Inscription range {start}-{end} is longer than {MAX_ID_RANGE} ids"
+            ));
+        }
+        Ok((start..=end).map(|idx| InscriptionId(txid, idx)).collect())
+    }
+}
+
+/// Most ids a single `<txid>i<start>-<end>` line of an id file may expand to
+const MAX_ID_RANGE: usize = 10_000;
+
+/// Read inscription ids from a file, one id or id range per line. Blank lines and lines starting
+/// with `#` are ignored. Malformed lines are reported on stderr and skipped.
+pub fn read_id_file(path: &PathBuf) -> anyhow::Result<Vec<InscriptionId>> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut ids = Vec::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match InscriptionId::parse_line(line) {
+            Ok(parsed) => ids.extend(parsed),
+            Err(e) => eprintln!("Skipping line {}: {e} ({line})", lineno + 1),
+        }
+    }
+    Ok(ids)
+}
+
 impl std::fmt::Display for InscriptionId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}i{}", self.0, self.1)
     }
 }
 
+/// Options controlling how inscription data is classified
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Minimum ratio of printable characters for UTF-8 data to be treated as text
+    pub text_threshold: f64,
+
+    /// Only look for inscriptions in taproot script path spends, skipping transactions without
+    /// any before attempting to parse them
+    pub taproot_only: bool,
+
+    /// Inscription bodies are cut off at this many bytes, so that adversarial witness data can't
+    /// make extraction allocate without bound
+    pub max_inscription_bytes: usize,
+
+    /// Whether the declared mime type or the content wins when they disagree
+    pub prefer: Preference,
+}
+
+/// Which side wins when an inscription's declared mime type and its content disagree about what
+/// it is:
+///
+/// | Declared            | Content              | Balanced | Mime                      | Content |
+/// |---------------------|----------------------|----------|---------------------------|---------|
+/// | `image/*`           | printable text       | text     | image if it decodes       | text    |
+/// | `text/*`, JSON      | a decodable image    | image    | text, decoded lossily     | image   |
+/// | `text/*`, JSON      | unprintable bytes    | binary   | text, decoded lossily     | binary  |
+/// | HTML                | text that isn't HTML | HTML     | HTML                      | text    |
+/// | anything but HTML   | HTML markup          | text     | text                      | HTML    |
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    /// Content is sniffed as text first, but HTML and charsets come from the mime type
+    #[default]
+    Balanced,
+    /// The declared mime type wins
+    Mime,
+    /// The content wins, and images are sniffed before text
+    Content,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            text_threshold: 0.8,
+            taproot_only: false,
+            // Well above the largest inscription a standard 4 MB block can hold
+            max_inscription_bytes: 16 * 1024 * 1024,
+            prefer: Preference::Balanced,
+        }
+    }
+}
+
+/// Options controlling how inscriptions are printed to the terminal
+#[derive(Debug, Default, Clone)]
+pub struct PrintOptions {
+    /// Print JSON as unformatted plain text
+    pub raw: bool,
+
+    /// Color scheme for JSON
+    pub theme: Theme,
+
+    /// Render short text as a QR code
+    pub qr: bool,
+
+    /// Syntax highlight code and markup by its declared mime type
+    pub highlight: bool,
+
+    /// Open audio in the system's default player
+    pub play: bool,
+
+    /// Frame of animated GIFs to show, counting from 1. Without it, animations are played
+    pub frame: Option<u32>,
+
+    /// Bytes per row of binary hexdumps, 16 when not set
+    pub hex_width: Option<usize>,
+
+    /// Output must not contain ANSI escape codes, so images are described rather than rendered
+    pub strip_ansi: bool,
+
+    /// Size of rendered images
+    pub layout: ImageLayout,
+}
+
+/// Inscriptions at least this large show a progress bar while being written to disk
+const PROGRESS_MIN_BYTES: usize = 1024 * 1024;
+
+/// Size of each write when showing progress
+const PROGRESS_CHUNK_BYTES: usize = 64 * 1024;
+
 #[derive(Clone)]
 pub struct Inscription {
     pub txid: Txid,
+    /// Index of the transaction input whose witness contains the inscription
+    pub input: usize,
     pub index: usize,
     pub mime: String,
     pub data: Vec<u8>,
     pub parsed: ParsedData,
+    /// The body was larger than the extraction limit, and `data` holds only its start
+    pub truncated: bool,
+    /// The `content-encoding` declared in the envelope, such as `gzip`
+    pub content_encoding: Option<String>,
+    /// The body as stored on chain, when it was decoded according to its content encoding.
+    /// `data` then holds the decoded content.
+    pub encoded_data: Option<Vec<u8>>,
+    /// The envelope's CBOR metadata, decoded as JSON
+    pub metadata: Option<serde_json::Value>,
+    /// The metaprotocol the inscription declares, such as `sns`
+    pub metaprotocol: Option<String>,
+    /// The inscriptions this one declares as its parents
+    pub parents: Vec<InscriptionId>,
+    /// The inscription whose content should be shown in place of this one's
+    pub delegate: Option<InscriptionId>,
+    /// Offset of the sat the inscription is made on among the reveal transaction's outputs, when
+    /// it declares one rather than using the first sat
+    pub pointer: Option<u64>,
+    /// When found by `--include-transfers`, the transaction that moved the inscription rather
+    /// than revealed it
+    pub transferred_by: Option<Txid>,
 }
 
 impl std::fmt::Display for Inscription {
@@ -89,99 +375,374 @@ impl std::fmt::Display for Inscription {
 }
 
 impl Inscription {
-    pub fn extract_all(tx: &Transaction) -> anyhow::Result<Vec<Arc<Inscription>>> {
+    pub fn extract_all(
+        tx: &Transaction,
+        opts: &ParseOptions,
+    ) -> anyhow::Result<Vec<Arc<Inscription>>> {
         let mut inscriptions = Vec::with_capacity(1);
+        if opts.taproot_only && !tx.input.iter().any(is_script_path_spend) {
+            return Ok(inscriptions);
+        }
+        // Like ord, number inscriptions across the whole transaction rather than per input
         for (idx, _) in tx.input.iter().enumerate() {
-            inscriptions.extend(Inscription::extract_witness(tx, idx)?);
+            let first_index = inscriptions.len();
+            inscriptions.extend(Inscription::extract_input(tx, idx, opts, first_index)?);
         }
         Ok(inscriptions)
     }
 
+    /// Find the inscription an id refers to in its reveal transaction. Ids number inscriptions
+    /// across every input, so `<txid>i1` may be the second envelope of the first input.
+    pub(crate) fn find(
+        tx: &Transaction,
+        inscription_id: &InscriptionId,
+        opts: &ParseOptions,
+    ) -> anyhow::Result<Arc<Inscription>> {
+        Inscription::extract_all(tx, opts)?
+            .into_iter()
+            .find(|inscription| inscription.index == inscription_id.1)
+            .ok_or_else(|| anyhow!("Inscription not found"))
+    }
+
+    /// Media types of every inscription in the transaction, without parsing their content
+    pub fn mimes(tx: &Transaction) -> Vec<String> {
+        tx.input
+            .iter()
+            .filter_map(|txin| {
+                extract_inscription(txin, ParseOptions::default().max_inscription_bytes)
+            })
+            .flatten()
+            .map(|envelope| envelope.mime)
+            .collect()
+    }
+
+    /// Extract the inscriptions of a single input. They are numbered from 0, as if the input
+    /// was the only one in the transaction.
     pub fn extract_witness(
         tx: &Transaction,
         input: usize,
+        opts: &ParseOptions,
+    ) -> anyhow::Result<Vec<Arc<Inscription>>> {
+        Inscription::extract_input(tx, input, opts, 0)
+    }
+
+    /// Extract the inscriptions of a single input, numbering them from `first_index`
+    fn extract_input(
+        tx: &Transaction,
+        input: usize,
+        opts: &ParseOptions,
+        first_index: usize,
     ) -> anyhow::Result<Vec<Arc<Inscription>>> {
         let txin = tx
             .input
             .get(input)
             .ok_or_else(|| anyhow!("Missing input"))?;
-        if let Some(inscriptions) = extract_inscription(txin) {
-            let arc_ins = inscriptions
-                .into_iter()
-                .enumerate()
-                .map(|(index, (mime, data))| {
-                    let parsed = parse_data(&data, &mime);
-                    Arc::new(Inscription {
-                        txid: tx.txid(),
-                        index,
-                        mime,
-                        data,
-                        parsed,
-                    })
-                })
-                .collect();
-            return Ok(arc_ins);
+        if opts.taproot_only && !is_script_path_spend(txin) {
+            return Ok(Vec::new());
         }
-        Ok(Vec::new())
+        let envelopes = extract_inscription(txin, opts.max_inscription_bytes).unwrap_or_default();
+        // Hashing the transaction is costly, so only do it for inputs with inscriptions
+        if envelopes.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(Inscription::from_envelopes(
+            envelopes,
+            tx.txid(),
+            input,
+            first_index,
+            opts,
+        ))
+    }
+
+    /// Extract the inscriptions in a tapscript, such as one that has not been spent yet. They
+    /// are given `txid` and `input` as the place the script is spent, and numbered from 0.
+    pub fn extract_script(
+        script: &Script,
+        txid: Txid,
+        input: usize,
+        opts: &ParseOptions,
+    ) -> Vec<Arc<Inscription>> {
+        let envelopes = extract_script(script, opts.max_inscription_bytes);
+        Inscription::from_envelopes(envelopes, txid, input, 0, opts)
     }
 
-    pub fn print(&self, raw_json: bool) -> anyhow::Result<()> {
+    /// Decode and classify the content of envelopes found in an input, numbering the
+    /// inscriptions from `first_index`
+    fn from_envelopes(
+        envelopes: Vec<Envelope>,
+        txid: Txid,
+        input: usize,
+        first_index: usize,
+        opts: &ParseOptions,
+    ) -> Vec<Arc<Inscription>> {
+        envelopes
+            .into_iter()
+            .enumerate()
+            .map(|(offset, envelope)| {
+                let Envelope {
+                    mime,
+                    data,
+                    encoding,
+                    metadata,
+                    metaprotocol,
+                    parents,
+                    delegate,
+                    pointer,
+                    mut truncated,
+                } = envelope;
+                let decoded = encoding
+                    .as_deref()
+                    .map(|encoding| decode_content(&data, encoding, opts.max_inscription_bytes));
+                // Bodies that fail to decode are kept as they are
+                let (data, encoded_data) = match decoded {
+                    Some(Ok((decoded, cut))) => {
+                        truncated |= cut;
+                        (decoded, Some(data))
+                    }
+                    _ => (data, None),
+                };
+                let parsed = parse_data(&data, &mime, opts);
+                Arc::new(Inscription {
+                    txid,
+                    input,
+                    index: first_index + offset,
+                    mime,
+                    data,
+                    parsed,
+                    truncated,
+                    content_encoding: encoding,
+                    encoded_data,
+                    metadata: decode_metadata(&metadata),
+                    metaprotocol,
+                    parents,
+                    delegate,
+                    pointer,
+                    transferred_by: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Print the inscription to `out`. Images are always rendered directly to stdout, after
+    /// flushing `out`.
+    pub fn print(&self, out: &mut impl Write, opts: &PrintOptions) -> anyhow::Result<()> {
+        if let Some(metadata) = &self.metadata {
+            writeln!(out, "{}", "metadata:".dark_grey())?;
+            print_json(out, metadata, opts)?;
+        }
         match &self.parsed {
-            ParsedData::Binary => println!("{}", hex::encode(self.data.as_bytes())),
-            ParsedData::Html(text) | ParsedData::Text(text) => println!("{text}"),
-            ParsedData::Image(image) => print_image(image)?,
-            ParsedData::Json(value) => print_json(value, raw_json)?,
+            ParsedData::Audio(format) => self.print_audio(out, *format, opts)?,
+            ParsedData::Binary if opts.raw => writeln!(out, "{}", hex::encode(&self.data))?,
+            ParsedData::Binary => hexdump(out, &self.data, opts.hex_width.unwrap_or(HEX_WIDTH))?,
+            ParsedData::Text(text) if opts.qr => print_qr(out, text)?,
+            ParsedData::Html(html) => {
+                let scripts = script_refs(html);
+                if !scripts.is_empty() {
+                    writeln!(out, "(generative, references {} scripts)", scripts.len())?;
+                }
+                print_text(out, html, &self.mime, opts)?
+            }
+            ParsedData::Text(text) => print_text(out, text, &self.mime, opts)?,
+            ParsedData::Image(image, format) if opts.strip_ansi => writeln!(
+                out,
+                "({format:?} image, {}x{})",
+                image.width(),
+                image.height()
+            )?,
+            ParsedData::Image(image, ImageFormat::Gif) => {
+                out.flush()?;
+                print_gif(&self.data, image, opts)?
+            }
+            ParsedData::Image(image, _) => {
+                out.flush()?;
+                print_images(std::slice::from_ref(image), &opts.layout)?
+            }
+            ParsedData::Json(value) => match self.parsed.brc20_op() {
+                Some(op) => print_brc20(out, &op, value, opts)?,
+                None => print_json(out, value, opts)?,
+            },
+            ParsedData::Pdf => print_pdf(out, &self.data, opts)?,
+            ParsedData::Svg(svg) if opts.strip_ansi => {
+                writeln!(out, "(SVG image, {} bytes)", svg.len())?
+            }
+            ParsedData::Svg(svg) => match rasterize_svg(svg) {
+                Ok(image) => {
+                    out.flush()?;
+                    print_images(std::slice::from_ref(&image), &opts.layout)?
+                }
+                Err(e) => {
+                    writeln!(out, "(Unable to render SVG: {e})")?;
+                    writeln!(out, "{svg}")?
+                }
+            },
         }
 
         Ok(())
     }
 
-    pub fn write_to_file(&self, path: &PathBuf) -> anyhow::Result<()> {
+    /// A copy of the inscription with JSON data replaced by its canonical form, so that
+    /// extracted files are byte for byte identical across tools. Other data is unchanged.
+    pub fn with_canonical_json(&self) -> Inscription {
+        let mut inscription = self.clone();
+        if let ParsedData::Json(value) = &self.parsed {
+            inscription.data = canonical_json(value).to_string().into_bytes();
+        }
+        inscription
+    }
+
+    /// Write the inscription's content to `path`. With `original`, a body that was decoded is
+    /// written as it is stored on chain instead.
+    pub fn write_to_file(&self, path: &PathBuf, original: bool) -> anyhow::Result<()> {
         match path.parent() {
             Some(dir) if !dir.exists() => std::fs::create_dir_all(dir)?,
             _ => {}
         }
-        std::fs::write(path, &self.data)?;
+        let data = match &self.encoded_data {
+            Some(encoded) if original => encoded,
+            _ => &self.data,
+        };
+        if data.len() < PROGRESS_MIN_BYTES || !std::io::stderr().is_tty() {
+            std::fs::write(path, data)?;
+            return Ok(());
+        }
+
+        let progress = ProgressBar::new(data.len() as u64).with_style(
+            ProgressStyle::with_template("{msg} [{bar:30}] {bytes}/{total_bytes}")?
+                .progress_chars("=> "),
+        );
+        progress.set_message(self.file_name());
+        let mut file = std::fs::File::create(path)?;
+        for chunk in data.chunks(PROGRESS_CHUNK_BYTES) {
+            file.write_all(chunk)?;
+            progress.inc(chunk.len() as u64);
+        }
+        file.flush()?;
+        progress.finish_and_clear();
         Ok(())
     }
 
+    /// File name used when extracting the inscription, `<INSCRIPTION_ID>.<extension>`
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.inscription_id(), self.file_extension())
+    }
+
     /// Guess file extension for file based on data heuristic
     pub fn file_extension(&self) -> String {
         match self.parsed {
+            ParsedData::Audio(format) => format.extension().into(),
             ParsedData::Binary => "dat".into(),
             ParsedData::Html(_) => "html".into(),
-            ParsedData::Image(_) => image::guess_format(&self.data)
-                .map(ImageFormat::extensions_str)
-                .unwrap_or_default()
-                .first()
-                .unwrap_or(&"dat")
-                .to_string(),
+            ParsedData::Image(..) => self.parsed.detected_format().unwrap_or("dat").to_string(),
             ParsedData::Json(_) => "json".into(),
+            ParsedData::Pdf => "pdf".into(),
+            ParsedData::Svg(_) => "svg".into(),
             ParsedData::Text(_) => "txt".into(),
         }
     }
 
-    /// Open an inscription the default indexer
-    pub fn open_web(&self) -> anyhow::Result<()> {
-        open::that(format!(
-            "https://ordinals.com/inscription/{}",
-            self.inscription_id(),
-        ))?;
+    /// Print a summary of an audio inscription, and play it with `--play`
+    fn print_audio(
+        &self,
+        out: &mut impl Write,
+        format: AudioFormat,
+        opts: &PrintOptions,
+    ) -> anyhow::Result<()> {
+        let size = format_size(self.data.len(), false);
+        match AudioSummary::parse(&self.data) {
+            Ok(AudioSummary { duration, bitrate }) => {
+                let bitrate = bitrate
+                    .map(|kbps| format!(", {kbps} kbps"))
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "({format} audio, {:.1}s{bitrate}, {size})",
+                    duration.as_secs_f64()
+                )?
+            }
+            Err(_) => writeln!(out, "({format} audio, {size})")?,
+        }
+        if opts.play {
+            let path = std::env::temp_dir().join(self.file_name());
+            std::fs::write(&path, &self.data)?;
+            open::that(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Open the inscription's page on a web explorer
+    pub fn open_web(&self, explorer: &ExplorerUrl) -> anyhow::Result<()> {
+        open::that(explorer.url(&self.inscription_id()))?;
         Ok(())
     }
 
+    /// The transaction input the inscription was found in, `<TXID>:<INPUT>`
+    pub fn source(&self) -> String {
+        format!("{}:{}", self.txid, self.input)
+    }
+
+    /// The declared content encoding, or `identity` if the body is stored as is
+    pub fn encoding(&self) -> &str {
+        self.content_encoding.as_deref().unwrap_or("identity")
+    }
+
+    /// Size of the body as stored on chain. It differs from the size of `data` when the body was
+    /// decoded according to its content encoding.
+    pub fn encoded_size(&self) -> usize {
+        self.encoded_data.as_ref().map_or(self.data.len(), Vec::len)
+    }
+
     pub fn inscription_id(&self) -> String {
         format!("{}i{}", self.txid, self.index)
     }
 }
 
-fn extract_inscription(txin: &TxIn) -> Option<Vec<(String, Vec<u8>)>> {
+/// Inscriptions can only be revealed by a taproot script path spend, whose witness ends with a
+/// tapscript and a control block (ignoring any annex)
+fn is_script_path_spend(txin: &TxIn) -> bool {
+    let witness = &txin.witness;
+    let len = witness.len();
+    let control_block = match witness.last() {
+        Some(last) if len >= 3 && last.first() == Some(&TAPROOT_ANNEX_PREFIX) => {
+            witness.nth(len - 2)
+        }
+        Some(_) if len >= 2 => witness.last(),
+        _ => None,
+    };
+    control_block.is_some_and(|cb| {
+        cb.len() >= 33
+            && (cb.len() - 33) % 32 == 0
+            && cb[0] & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT
+    })
+}
+
+/// The fields pulled out of a single inscription envelope
+#[derive(Debug, PartialEq)]
+struct Envelope {
+    mime: String,
+    data: Vec<u8>,
+    /// The content encoding field, if present
+    encoding: Option<String>,
+    /// CBOR metadata, empty if there is none
+    metadata: Vec<u8>,
+    /// The metaprotocol field, if present
+    metaprotocol: Option<String>,
+    /// Parent ids, from every parent field that could be decoded
+    parents: Vec<InscriptionId>,
+    /// The first delegate field, if present
+    delegate: Option<InscriptionId>,
+    /// The first pointer field that could be decoded
+    pointer: Option<u64>,
+    /// The body was cut off at the extraction limit
+    truncated: bool,
+}
+
+fn extract_inscription(txin: &TxIn, max_bytes: usize) -> Option<Vec<Envelope>> {
     let tapscript = txin.witness.tapscript()?;
-    let inscriptions = extract_script(tapscript);
+    let inscriptions = extract_script(tapscript, max_bytes);
     Some(inscriptions)
 }
 
-fn extract_script(script: &Script) -> Vec<(String, Vec<u8>)> {
+fn extract_script(script: &Script, max_bytes: usize) -> Vec<Envelope> {
     let instructions: Result<VecDeque<_>, _> = script.instructions().collect();
     let mut inscriptions = Vec::new();
     if instructions.is_err() {
@@ -189,38 +750,58 @@ fn extract_script(script: &Script) -> Vec<(String, Vec<u8>)> {
     }
     let mut instructions = instructions.unwrap();
 
-    while !instructions.is_empty() {
-        if extract_op0(&mut instructions).is_none() {
-            continue;
-        }
-
-        if extract_opif(&mut instructions).is_none() {
-            continue;
-        }
+    // Most tapscripts are not inscriptions, so don't walk the script unless there is an `ord`
+    // marker somewhere in it
+    if !instructions.iter().any(is_ord_marker) {
+        return inscriptions;
+    }
 
-        if extract_ord(&mut instructions).is_none() {
-            continue;
+    while !instructions.is_empty() {
+        let remaining = instructions.len();
+        if let Some(inscription) = extract_envelope(&mut instructions, max_bytes) {
+            inscriptions.push(inscription);
         }
 
-        if extract_push1(&mut instructions).is_none() {
-            continue;
+        // A failed envelope must consume at least one instruction, so that this always terminates
+        if instructions.len() == remaining {
+            instructions.pop_front();
         }
+    }
 
-        if let Some(media_type) = extract_media_type(&mut instructions) {
-            if extract_until_op0(&mut instructions).is_none() {
-                continue;
-            }
-            let data = extract_data(&mut instructions);
+    inscriptions
+}
 
-            if extract_opendif(&mut instructions).is_none() {
-                continue;
-            }
+/// Media type for inscriptions that don't declare one
+const UNTYPED_MEDIA_TYPE: &str = "application/octet-stream";
 
-            inscriptions.push((media_type, data));
-        }
-    }
+fn extract_envelope(
+    instructions: &mut VecDeque<Instruction<'_>>,
+    max_bytes: usize,
+) -> Option<Envelope> {
+    extract_op0(instructions)?;
+    extract_opif(instructions)?;
+    extract_ord(instructions)?;
+    let fields = extract_fields(instructions);
+    let (data, truncated) = extract_data(instructions, max_bytes);
+    extract_opendif(instructions)?;
+    Some(Envelope {
+        // Minimal envelopes may leave out the content type
+        mime: fields.mime.unwrap_or_else(|| UNTYPED_MEDIA_TYPE.into()),
+        data,
+        encoding: fields.encoding,
+        metadata: fields.metadata,
+        metaprotocol: fields.metaprotocol,
+        parents: fields.parents,
+        delegate: fields.delegate,
+        pointer: fields.pointer,
+        truncated,
+    })
+}
 
-    inscriptions
+fn is_ord_marker(instruction: &Instruction<'_>) -> bool {
+    instruction
+        .push_bytes()
+        .is_some_and(|b| b.as_bytes() == b"ord")
 }
 
 fn extract_op0(script: &mut VecDeque<Instruction<'_>>) -> Option<()> {
@@ -244,148 +825,1153 @@ fn extract_ord(script: &mut VecDeque<Instruction<'_>>) -> Option<()> {
     None
 }
 
-fn extract_push1(script: &mut VecDeque<Instruction<'_>>) -> Option<()> {
-    if script.pop_front()?.push_bytes()?.as_bytes() == [1] {
-        return Some(());
-    }
-    None
+/// Envelope tag for the media type of the body
+const CONTENT_TYPE_TAG: u8 = 1;
+
+/// Envelope tag for the output sat the inscription is made on
+const POINTER_TAG: u8 = 2;
+
+/// Envelope tag for the id of a parent inscription, which may appear more than once
+const PARENT_TAG: u8 = 3;
+
+/// Envelope tag for CBOR encoded metadata
+const METADATA_TAG: u8 = 5;
+
+/// Envelope tag for the name of the metaprotocol an inscription follows
+const METAPROTOCOL_TAG: u8 = 7;
+
+/// Envelope tag for the content encoding of the body
+const CONTENT_ENCODING_TAG: u8 = 9;
+
+/// Envelope tag for the id of an inscription whose content is shown in place of this one's
+const DELEGATE_TAG: u8 = 11;
+
+/// Most delegate links followed when resolving content, which also stops delegate loops
+pub(crate) const MAX_DELEGATE_DEPTH: usize = 4;
+
+/// The envelope fields that ortty reads, between the protocol id and the body
+#[derive(Debug, Default, PartialEq)]
+struct Fields {
+    mime: Option<String>,
+    encoding: Option<String>,
+    /// Raw CBOR, concatenated from every metadata field
+    metadata: Vec<u8>,
+    metaprotocol: Option<String>,
+    parents: Vec<InscriptionId>,
+    delegate: Option<InscriptionId>,
+    pointer: Option<u64>,
 }
 
-fn extract_until_op0(script: &mut VecDeque<Instruction<'_>>) -> Option<()> {
-    while !script.is_empty() {
-        if script.pop_front()?.push_bytes()?.is_empty() {
-            return Some(());
+/// Read the tag and value pairs after the protocol id, in any order, into the fields ortty
+/// knows: content type, content encoding, metadata, metaprotocol, parents, delegate and pointer.
+/// Other tags are skipped. The fields end at the empty push that separates them from the body,
+/// which is consumed, or at `OP_ENDIF` in envelopes without a body, such as those that only name
+/// a delegate. A push that can't be a tag, a single byte followed by its value, also ends them,
+/// since minimal envelopes go straight from the protocol id to the body.
+fn extract_fields(script: &mut VecDeque<Instruction<'_>>) -> Fields {
+    let mut fields = Fields::default();
+    loop {
+        let tag = match script.front().and_then(Instruction::push_bytes) {
+            Some(separator) if separator.is_empty() => {
+                script.pop_front();
+                return fields;
+            }
+            Some(tag)
+                if tag.len() == 1 && script.get(1).and_then(Instruction::push_bytes).is_some() =>
+            {
+                tag.as_bytes()[0]
+            }
+            _ => return fields,
+        };
+        script.pop_front();
+        let Some(Instruction::PushBytes(value)) = script.pop_front() else {
+            return fields;
+        };
+        let value = value.as_bytes();
+        match tag {
+            CONTENT_TYPE_TAG if fields.mime.is_none() => {
+                fields.mime = std::str::from_utf8(value).ok().map(Into::into)
+            }
+            CONTENT_ENCODING_TAG => {
+                fields.encoding = std::str::from_utf8(value).ok().map(Into::into)
+            }
+            // Metadata too large for one push is split across several metadata fields
+            METADATA_TAG => fields.metadata.extend(value),
+            PARENT_TAG => fields
+                .parents
+                .extend(InscriptionId::from_envelope_value(value)),
+            DELEGATE_TAG if fields.delegate.is_none() => {
+                fields.delegate = InscriptionId::from_envelope_value(value)
+            }
+            METAPROTOCOL_TAG => {
+                fields.metaprotocol = std::str::from_utf8(value).ok().map(Into::into)
+            }
+            POINTER_TAG if fields.pointer.is_none() => fields.pointer = decode_pointer(value),
+            _ => {}
         }
     }
-    None
 }
 
-fn extract_media_type(script: &mut VecDeque<Instruction<'_>>) -> Option<String> {
-    script
-        .pop_front()?
-        .push_bytes()
-        .and_then(|b| std::str::from_utf8(b.as_bytes()).ok())
-        .map(Into::into)
+/// Decode a pointer, a little endian integer with trailing zeros left out. Like ord, pointers
+/// too large for a `u64` are ignored.
+fn decode_pointer(value: &[u8]) -> Option<u64> {
+    let (value, rest) = value.split_at(value.len().min(8));
+    if rest.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    let mut bytes = [0; 8];
+    bytes[..value.len()].copy_from_slice(value);
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Decode CBOR metadata as JSON. Metadata that isn't valid CBOR, or can't be represented as
+/// JSON, such as maps with non-string keys, is ignored.
+fn decode_metadata(metadata: &[u8]) -> Option<serde_json::Value> {
+    if metadata.is_empty() {
+        return None;
+    }
+    ciborium::from_reader(metadata).ok()
 }
 
 fn extract_opendif(script: &mut VecDeque<Instruction<'_>>) -> Option<()> {
-    if script.get(0)?.opcode()? == OP_ENDIF {
+    if script.front()?.opcode()? == OP_ENDIF {
         script.pop_front();
         return Some(());
     }
     None
 }
 
-fn extract_data(instructions: &mut VecDeque<Instruction<'_>>) -> Vec<u8> {
+/// Concatenate the body pushes, keeping at most `max_bytes`. Pushes past the limit are still
+/// consumed so the envelope can be closed, and the data is flagged as truncated.
+fn extract_data(instructions: &mut VecDeque<Instruction<'_>>, max_bytes: usize) -> (Vec<u8>, bool) {
     let mut data = Vec::new();
-    while let Some(ins) = instructions.get(0) {
+    let mut truncated = false;
+    while let Some(ins) = instructions.front() {
         match ins {
-            Instruction::PushBytes(pb) => data.extend(pb.as_bytes()),
+            Instruction::PushBytes(pb) => {
+                let room = max_bytes - data.len();
+                let bytes = pb.as_bytes();
+                if bytes.len() > room {
+                    truncated = true;
+                }
+                data.extend(&bytes[..bytes.len().min(room)]);
+            }
             Instruction::Op(_) => break,
         }
         instructions.pop_front();
     }
-    data
+    (data, truncated)
+}
+
+/// Decode a body stored with a content encoding, keeping at most `max_bytes` of the decoded
+/// data. Returns the decoded data and whether it was cut off. Only `gzip` is supported.
+pub fn decode_content(
+    data: &[u8],
+    encoding: &str,
+    max_bytes: usize,
+) -> anyhow::Result<(Vec<u8>, bool)> {
+    let mut decoded = Vec::new();
+    match encoding.to_lowercase().as_ref() {
+        "gzip" => GzDecoder::new(data)
+            .take(max_bytes as u64 + 1)
+            .read_to_end(&mut decoded)?,
+        _ => return Err(anyhow!("Unsupported content encoding {encoding}")),
+    };
+    let truncated = decoded.len() > max_bytes;
+    decoded.truncate(max_bytes);
+    Ok((decoded, truncated))
+}
+
+/// Classify inscription data. Text is detected first, using the declared mime type only to tell
+/// HTML apart from other text, followed by JSON. Anything else is sniffed as an image, falling
+/// back to binary.
+pub fn parse_data(data: &[u8], mime: &str, opts: &ParseOptions) -> ParsedData {
+    classify(data, mime, opts, &mut |_| {})
 }
 
-fn parse_data(data: &[u8], mime: &str) -> ParsedData {
-    if let Ok(text) = std::str::from_utf8(data) {
-        if mime.to_lowercase().contains("html") {
+/// Parse the data like [`parse_data`], also describing each decision made along the way.
+pub fn explain_data(data: &[u8], mime: &str, opts: &ParseOptions) -> (ParsedData, Vec<String>) {
+    let mut steps = Vec::new();
+    let parsed = classify(data, mime, opts, &mut |step| steps.push(step.to_string()));
+    (parsed, steps)
+}
+
+fn classify(
+    data: &[u8],
+    mime: &str,
+    opts: &ParseOptions,
+    note: &mut dyn FnMut(std::fmt::Arguments),
+) -> ParsedData {
+    note(format_args!("declared mime: {mime}"));
+    // PDFs can be entirely printable, so they are recognized before looking for text
+    if data.starts_with(PDF_MAGIC) {
+        note(format_args!("found PDF magic bytes"));
+        return ParsedData::Pdf;
+    }
+    let declared = mime.to_lowercase();
+    let declared_image = declared.starts_with("image/");
+    let declared_text = declared.starts_with("text/") || declared.contains("json");
+    let image_first = match opts.prefer {
+        Preference::Balanced => false,
+        Preference::Mime => declared_image,
+        Preference::Content => true,
+    };
+    if image_first {
+        note(format_args!("checking for an image before text"));
+        if let Some(image) = sniff_image(data, note) {
+            return image;
+        }
+    }
+
+    let decoded = match std::str::from_utf8(data) {
+        Ok(text) => {
+            note(format_args!("UTF-8 decoding succeeded"));
+            Some(Cow::Borrowed(text))
+        }
+        Err(e) => {
+            note(format_args!("UTF-8 decoding failed: {e}"));
+            decode_charset(data, mime, note)
+        }
+    };
+    let text = decoded.filter(|text| {
+        let ratio = printable_ratio(text);
+        let passes = ratio >= opts.text_threshold;
+        note(format_args!(
+            "{:.0}% printable ({} threshold of {:.0}%)",
+            ratio * 100.0,
+            if passes { "meets" } else { "below" },
+            opts.text_threshold * 100.0
+        ));
+        passes
+    });
+    let text = text.as_deref();
+    if let Some(text) = text {
+        if declared.split(';').next().map(str::trim) == Some("image/svg+xml") {
+            note(format_args!("declared mime is SVG"));
+            return ParsedData::Svg(text.into());
+        }
+        let html = match opts.prefer {
+            Preference::Content => looks_like_html(text),
+            _ => declared.contains("html"),
+        };
+        if html {
+            match opts.prefer {
+                Preference::Content => note(format_args!("content is HTML markup")),
+                _ => note(format_args!("declared mime is HTML")),
+            }
             return ParsedData::Html(text.into());
-        } else if let Ok(value) = serde_json::from_str(text) {
-            return ParsedData::Json(value);
-        } else {
-            return ParsedData::Text(text.into());
+        }
+        match serde_json::from_str(text) {
+            Ok(value) => {
+                note(format_args!("JSON parsing succeeded"));
+                return ParsedData::Json(value);
+            }
+            Err(e) => {
+                note(format_args!("JSON parsing failed: {e}"));
+                return ParsedData::Text(text.into());
+            }
+        }
+    }
+
+    if opts.prefer == Preference::Mime && declared_text {
+        note(format_args!("declared mime is text, decoding lossily"));
+        return ParsedData::Text(String::from_utf8_lossy(data).into());
+    }
+
+    if !image_first {
+        if let Some(image) = sniff_image(data, note) {
+            return image;
         }
     }
 
-    if let Ok(image) = image::load_from_memory(data) {
-        return ParsedData::Image(image);
+    if let Some(format) = AudioFormat::sniff(data, &declared) {
+        note(format_args!("recognized {format} audio"));
+        return ParsedData::Audio(format);
     }
 
     ParsedData::Binary
 }
 
-fn print_image(image: &DynamicImage) -> anyhow::Result<()> {
-    let config = viuer::Config {
-        absolute_offset: false,
-        y: 1,
-        width: Some(40),
-        ..Default::default()
-    };
-    viuer::print(image, &config)?;
-    Ok(())
+/// Decode the data as an image, if it is in a recognized image format
+fn sniff_image(data: &[u8], note: &mut dyn FnMut(std::fmt::Arguments)) -> Option<ParsedData> {
+    match image::guess_format(data) {
+        Ok(format) => match image::load_from_memory_with_format(data, format) {
+            Ok(image) => {
+                note(format_args!("image decoding succeeded as {format:?}"));
+                return Some(ParsedData::Image(image, format));
+            }
+            Err(e) => note(format_args!("image decoding as {format:?} failed: {e}")),
+        },
+        Err(_) => note(format_args!("image format not recognized")),
+    }
+    None
 }
 
-fn print_json(value: &serde_json::Value, raw_json: bool) -> anyhow::Result<()> {
-    let formatted = if raw_json {
-        serde_json::to_string(value)?
-    } else {
-        to_colored_json(value, ColorMode::On)?
+/// Whether text starts like an HTML document
+fn looks_like_html(text: &str) -> bool {
+    let start: String = text.trim_start().chars().take(14).collect();
+    let start = start.to_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+/// Decode text in the charset declared by the mime type, such as `text/plain;charset=latin1`.
+/// Gives up if there is no charset, it is unknown, or the data is not valid in it.
+fn decode_charset<'a>(
+    data: &'a [u8],
+    mime: &str,
+    note: &mut dyn FnMut(std::fmt::Arguments),
+) -> Option<Cow<'a, str>> {
+    let charset = mime_charset(mime)?;
+    let Some(encoding) = Encoding::for_label(charset.as_bytes()) else {
+        note(format_args!("unknown charset {charset}"));
+        return None;
     };
-    println!("{formatted}");
-    Ok(())
+    let text = encoding.decode_without_bom_handling_and_without_replacement(data);
+    match &text {
+        Some(_) => note(format_args!("{} decoding succeeded", encoding.name())),
+        None => note(format_args!("{} decoding failed", encoding.name())),
+    }
+    text
 }
 
-pub(crate) fn fetch_and_print(
-    args: &crate::args::Args,
-    inscription_id: &InscriptionId,
-) -> anyhow::Result<()> {
-    let client = bitcoincore_rpc::Client::new(&args.rpc_host(), args.rpc_auth()?)?;
-    let tx = client.get_raw_transaction(&inscription_id.0, None)?;
-    let inscriptions = Inscription::extract_witness(&tx, inscription_id.1)
-        .map_err(|_| anyhow!("Inscription not found"))?;
-    for inscription in inscriptions {
-        inscription.print(args.raw())?;
+/// The `charset` parameter of a mime type, if it has one
+pub fn mime_charset(mime: &str) -> Option<&str> {
+    mime.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"'))
+    })
+}
+
+/// Rebuild JSON with the keys of every object sorted. Serializing the result with
+/// `to_string` leaves out all insignificant whitespace.
+fn canonical_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(canonical_json).collect(),
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.clone(), canonical_json(value)))
+                .collect()
+        }
+        value => value.clone(),
     }
-    println!();
+}
 
-    Ok(())
+/// Ratio of characters in the text that are printable, treating whitespace as printable
+fn printable_ratio(text: &str) -> f64 {
+    let (total, printable) = text.chars().fold((0, 0), |(total, printable), c| {
+        let is_printable = !c.is_control() || c.is_whitespace();
+        (total + 1, printable + usize::from(is_printable))
+    });
+    if total == 0 {
+        return 1.0;
+    }
+    printable as f64 / total as f64
 }
 
-#[cfg(test)]
-mod tests {
-    use bitcoin::opcodes::{all::OP_CHECKSIG, OP_FALSE};
+/// How images are arranged when printed together
+#[derive(Debug, Clone, Copy)]
+pub struct ImageLayout {
+    /// Width of each image, in terminal columns
+    pub width: u32,
 
-    use super::*;
+    /// Columns between images in the same row
+    pub gap: u32,
 
-    #[test]
-    fn test_normal_inscription() {
-        let script = bitcoin::script::Builder::new()
-            .push_opcode(OP_FALSE)
-            .push_opcode(OP_IF)
-            .push_slice(b"ord")
-            .push_slice([1])
-            .push_slice(b"text/plain")
-            .push_slice([])
-            .push_slice(b"hello world")
-            .push_opcode(OP_ENDIF)
-            .into_script();
-        let results = extract_script(&script);
-        assert_eq!(results.len(), 1);
-        assert_eq!(results, [("text/plain".into(), b"hello world".to_vec())]);
-    }
+    /// Height of each image, in terminal rows. Without it, the height follows the aspect ratio
+    pub height: Option<u32>,
 
-    #[test]
-    fn test_ignore_preceding() {
-        let script = bitcoin::script::Builder::new()
-            .push_opcode(OP_CHECKSIG)
-            .push_opcode(OP_FALSE)
-            .push_opcode(OP_IF)
-            .push_slice(b"ord")
-            .push_slice([1])
-            .push_slice(b"text/plain")
-            .push_slice([])
-            .push_slice(b"hello world")
-            .push_opcode(OP_ENDIF)
-            .into_script();
-        let results = extract_script(&script);
-        assert_eq!(results.len(), 1);
-        assert_eq!(results, [("text/plain".into(), b"hello world".to_vec())]);
+    /// How images are fitted into the box when `height` is set
+    pub fit: Fit,
+}
+
+impl Default for ImageLayout {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            gap: 2,
+            height: None,
+            fit: Fit::Contain,
+        }
     }
+}
 
-    #[test]
-    fn test_multiple_inscriptions_per_witness() {
+/// How an image is fitted into a box of a fixed width and height
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    /// Scale to fit inside the box, keeping the aspect ratio, with black bars filling the rest
+    #[default]
+    Contain,
+    /// Scale to fill the box, keeping the aspect ratio, cropping the overflow around the center
+    Cover,
+    /// Scale to the box exactly, distorting the image
+    Stretch,
+}
+
+impl std::fmt::Display for Fit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fit::Contain => f.write_str("contain"),
+            Fit::Cover => f.write_str("cover"),
+            Fit::Stretch => f.write_str("stretch"),
+        }
+    }
+}
+
+impl std::str::FromStr for Fit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fit = match s.to_lowercase().as_ref() {
+            "contain" => Self::Contain,
+            "cover" => Self::Cover,
+            "stretch" => Self::Stretch,
+            _ => return Err(anyhow!("Unknown fit")),
+        };
+        Ok(fit)
+    }
+}
+
+/// Resize an image to exactly fill a box of terminal cells. Each cell shows two pixels stacked
+/// vertically, so the box is `columns` by twice `rows` pixels.
+fn fit_image(image: &DynamicImage, columns: u32, rows: u32, fit: Fit) -> DynamicImage {
+    let (width, height) = (columns, rows * 2);
+    let filter = FilterType::Triangle;
+    match fit {
+        Fit::Contain => {
+            let scaled = image.resize(width, height, filter);
+            let mut boxed = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+            let x = (width - scaled.width()) / 2;
+            let y = (height - scaled.height()) / 2;
+            imageops::overlay(&mut boxed, &scaled.to_rgba8(), x.into(), y.into());
+            DynamicImage::ImageRgba8(boxed)
+        }
+        Fit::Cover => image.resize_to_fill(width, height, filter),
+        Fit::Stretch => image.resize_exact(width, height, filter),
+    }
+}
+
+/// Print images left to right, wrapping onto a new row when the terminal is full.
+pub fn print_images(images: &[DynamicImage], layout: &ImageLayout) -> anyhow::Result<()> {
+    let (columns, _) = crossterm::terminal::size().unwrap_or((80, 20));
+    let per_row = ((u32::from(columns) + layout.gap) / (layout.width + layout.gap)).max(1);
+    for row in images.chunks(per_row as usize) {
+        let mut row_height = 0;
+        let mut last_height = 0;
+        for (idx, image) in row.iter().enumerate() {
+            // Each image after the first starts back at the top of the row, to the right of the
+            // previous one
+            let config = viuer::Config {
+                absolute_offset: false,
+                x: (idx as u32 * (layout.width + layout.gap)) as u16,
+                y: if idx == 0 { 1 } else { -(last_height as i16) },
+                width: Some(layout.width),
+                height: layout.height,
+                ..Default::default()
+            };
+            let (_, height) = viuer::print(&fitted(image, layout), &config)?;
+            last_height = height;
+            row_height = row_height.max(height);
+        }
+
+        // Leave the cursor below the tallest image in the row
+        let mut out = stdout();
+        for _ in last_height..row_height {
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+/// The image as it is drawn, fitted into the box when a height is set
+fn fitted<'a>(image: &'a DynamicImage, layout: &ImageLayout) -> Cow<'a, DynamicImage> {
+    match layout.height {
+        Some(rows) => Cow::Owned(fit_image(image, layout.width, rows, layout.fit)),
+        None => Cow::Borrowed(image),
+    }
+}
+
+/// Frames decoded from an animated GIF, beyond which the rest are ignored
+const GIF_MAX_FRAMES: usize = 256;
+
+/// Animations are played at most this many times, and for at most `GIF_MAX_PLAY`, so they
+/// can't hold up a scan
+const GIF_MAX_LOOPS: usize = 3;
+
+const GIF_MAX_PLAY: Duration = Duration::from_secs(10);
+
+/// Frames with a shorter delay are shown for this long, as browsers do
+const GIF_MIN_DELAY: Duration = Duration::from_millis(20);
+
+/// Print a GIF, playing it if it is animated, or showing the frame chosen with `--frame`
+fn print_gif(data: &[u8], first: &DynamicImage, opts: &PrintOptions) -> anyhow::Result<()> {
+    let frames = gif_frames(data);
+    match chosen_frame(&frames, opts.frame) {
+        Some(frame) => {
+            let image = DynamicImage::ImageRgba8(frame.buffer().clone());
+            print_images(std::slice::from_ref(&image), &opts.layout)
+        }
+        None if opts.frame.is_none() && frames.len() > 1 => play_gif(&frames, &opts.layout),
+        None => print_images(std::slice::from_ref(first), &opts.layout),
+    }
+}
+
+/// The frames of a GIF, or none if they can't be decoded
+fn gif_frames(data: &[u8]) -> Vec<Frame> {
+    GifDecoder::new(Cursor::new(data))
+        .and_then(|decoder| {
+            decoder
+                .into_frames()
+                .take(GIF_MAX_FRAMES)
+                .collect::<ImageResult<_>>()
+        })
+        .unwrap_or_default()
+}
+
+/// The frame chosen with `--frame`, counting from 1, or the last if there are fewer
+fn chosen_frame(frames: &[Frame], frame: Option<u32>) -> Option<&Frame> {
+    let n = frame? as usize;
+    frames.get(n.saturating_sub(1)).or(frames.last())
+}
+
+/// Play an animation in place, drawing each frame over the previous one. Ctrl-C ends ortty as
+/// usual, since viuer leaves the terminal as it was after each frame.
+fn play_gif(frames: &[Frame], layout: &ImageLayout) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let mut height = 0;
+    let looped = frames.iter().cycle().take(frames.len() * GIF_MAX_LOOPS);
+    for (n, frame) in looped.enumerate() {
+        if started.elapsed() >= GIF_MAX_PLAY {
+            break;
+        }
+        let image = DynamicImage::ImageRgba8(frame.buffer().clone());
+        let config = viuer::Config {
+            absolute_offset: false,
+            y: if n == 0 { 1 } else { -(height as i16) },
+            width: Some(layout.width),
+            height: layout.height,
+            ..Default::default()
+        };
+        (_, height) = viuer::print(&fitted(&image, layout), &config)?;
+        stdout().flush()?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay = Duration::from_millis((numer / denom.max(1)).into());
+        std::thread::sleep(delay.max(GIF_MIN_DELAY));
+    }
+    Ok(())
+}
+
+/// Print the first page of a PDF when built with the `pdf-render` feature and pdfium is
+/// installed, otherwise its page count and document information
+#[cfg_attr(not(feature = "pdf-render"), allow(unused_variables))]
+fn print_pdf(out: &mut impl Write, data: &[u8], opts: &PrintOptions) -> anyhow::Result<()> {
+    #[cfg(feature = "pdf-render")]
+    if !opts.strip_ansi {
+        match crate::pdf::render_first_page(data) {
+            Ok(page) => {
+                out.flush()?;
+                return print_images(std::slice::from_ref(&page), &opts.layout);
+            }
+            Err(e) => writeln!(out, "(Unable to render PDF: {e})")?,
+        }
+    }
+
+    match PdfSummary::parse(data) {
+        Ok(summary) => {
+            writeln!(out, "(PDF document, {} pages)", summary.pages)?;
+            if let Some(title) = summary.title {
+                writeln!(out, "title: {title}")?;
+            }
+            if let Some(author) = summary.author {
+                writeln!(out, "author: {author}")?;
+            }
+        }
+        Err(e) => writeln!(out, "(PDF document, unreadable: {e})")?,
+    }
+    Ok(())
+}
+
+/// Length of the longest side of a rasterized SVG, in pixels
+const SVG_RENDER_PIXELS: f32 = 512.0;
+
+/// Rasterize an SVG so it can be printed like any other image. Scripts are never run, and
+/// `<image>` elements can only embed data URLs, so an inscription can't make rendering read local
+/// files.
+fn rasterize_svg(svg: &str) -> anyhow::Result<DynamicImage> {
+    let mut options = usvg::Options::default();
+    options.image_href_resolver.resolve_string = Box::new(|_, _| None);
+    Arc::make_mut(&mut options.fontdb).load_system_fonts();
+    let tree = usvg::Tree::from_str(svg, &options)?;
+    let size = tree.size();
+    let scale = SVG_RENDER_PIXELS / size.width().max(size.height());
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    let mut pixmap =
+        tiny_skia::Pixmap::new(width, height).ok_or_else(|| anyhow!("SVG has no area"))?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(image::load_from_memory_with_format(
+        &pixmap.encode_png()?,
+        ImageFormat::Png,
+    )?)
+}
+
+/// Print text, highlighted by its mime type with `--highlight` unless colors are disabled
+fn print_text(
+    out: &mut impl Write,
+    text: &str,
+    mime: &str,
+    opts: &PrintOptions,
+) -> anyhow::Result<()> {
+    let highlighted = if opts.highlight && !opts.strip_ansi {
+        highlight(text, mime, opts.theme)
+    } else {
+        None
+    };
+    writeln!(out, "{}", highlighted.as_deref().unwrap_or(text))?;
+    Ok(())
+}
+
+/// Largest text, in bytes, that will be rendered as a QR code
+const QR_MAX_BYTES: usize = 256;
+
+fn print_qr(out: &mut impl Write, text: &str) -> anyhow::Result<()> {
+    if text.len() > QR_MAX_BYTES {
+        writeln!(out, "(Text is too large for a QR code)")?;
+        writeln!(out, "{text}")?;
+        return Ok(());
+    }
+    // Colors are inverted, since terminals are usually light text on a dark background
+    let code = QrCode::new(text.as_bytes())?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+    writeln!(out, "{code}")?;
+    Ok(())
+}
+
+fn print_json(
+    out: &mut impl Write,
+    value: &serde_json::Value,
+    opts: &PrintOptions,
+) -> anyhow::Result<()> {
+    let formatted = if opts.raw {
+        serde_json::to_string(value)?
+    } else {
+        ColoredFormatter::with_styler(PrettyFormatter::new(), opts.theme.styler())
+            .to_colored_json(value, ColorMode::On)?
+    };
+    writeln!(out, "{formatted}")?;
+    Ok(())
+}
+
+/// Bytes per row of hexdumps unless `--hex-width` is set
+const HEX_WIDTH: usize = 16;
+
+/// Print `data` the way `xxd` does: each row has its offset, `width` bytes in hex in groups of
+/// two, and the same bytes as ASCII with unprintable ones as dots. Bytes are colored by class.
+fn hexdump(out: &mut impl Write, data: &[u8], width: usize) -> std::io::Result<()> {
+    for (row, chunk) in data.chunks(width).enumerate() {
+        write!(out, "{}:", format!("{:08x}", row * width).dark_grey())?;
+        for i in 0..width {
+            if i % 2 == 0 {
+                write!(out, " ")?;
+            }
+            match chunk.get(i) {
+                Some(byte) => write!(out, "{}", format!("{byte:02x}").with(byte_color(*byte)))?,
+                // Pad the last row so its ASCII lines up
+                None => write!(out, "  ")?,
+            }
+        }
+        write!(out, "  ")?;
+        for byte in chunk {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            };
+            write!(out, "{}", c.with(byte_color(*byte)))?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn byte_color(byte: u8) -> Color {
+    match byte {
+        0 => Color::DarkGrey,
+        b' ' => Color::Green,
+        _ if byte.is_ascii_graphic() => Color::Green,
+        _ if byte.is_ascii_whitespace() => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+/// Print a BRC-20 operation as a one line summary, such as `BRC-20 mint: ordi amt=1000`,
+/// followed by its JSON unless printing raw
+fn print_brc20(
+    out: &mut impl Write,
+    op: &Brc20Op,
+    value: &serde_json::Value,
+    opts: &PrintOptions,
+) -> anyhow::Result<()> {
+    let field = |name: &str, value: &str| format!(" {}={}", name.dark_grey(), value.cyan());
+    let (name, tick, fields) = match op {
+        Brc20Op::Deploy { tick, max, lim } => {
+            let lim = lim.as_deref().map(|lim| field("lim", lim));
+            ("deploy", tick, field("max", max) + &lim.unwrap_or_default())
+        }
+        Brc20Op::Mint { tick, amt } => ("mint", tick, field("amt", amt)),
+        Brc20Op::Transfer { tick, amt } => ("transfer", tick, field("amt", amt)),
+    };
+    writeln!(
+        out,
+        "{} {}: {}{fields}",
+        "BRC-20".magenta(),
+        name.bold(),
+        tick.as_str().yellow()
+    )?;
+    if !opts.raw {
+        print_json(out, value, opts)?;
+    }
+    Ok(())
+}
+
+/// Look up an inscription by id, from the ord server if `--ord-url` is set or the node otherwise
+pub(crate) fn fetch(
+    args: &crate::args::Args,
+    inscription_id: &InscriptionId,
+) -> anyhow::Result<Vec<Arc<Inscription>>> {
+    let inscriptions = match args.ord_url() {
+        Some(base) => vec![Arc::new(fetch_from_ord(
+            base,
+            inscription_id,
+            &args.parse_options(),
+        )?)],
+        None => {
+            let client = crate::chain::connect(args)?;
+            fetch_from_node(client.as_ref(), inscription_id, &args.parse_options())?
+        }
+    };
+    Ok(inscriptions)
+}
+
+/// Look up an inscription id in the node's copy of the reveal transaction
+pub(crate) fn fetch_from_node(
+    client: &dyn ChainSource,
+    inscription_id: &InscriptionId,
+    opts: &ParseOptions,
+) -> anyhow::Result<Vec<Arc<Inscription>>> {
+    let tx = client.get_raw_transaction(&inscription_id.0, None)?;
+    Ok(vec![Inscription::find(&tx, inscription_id, opts)?])
+}
+
+/// Follow an inscription's delegate links to the inscription whose content should be shown for
+/// it, looking each one up with `lookup`. Inscriptions without a delegate are their own content.
+pub(crate) fn resolve_delegate(
+    inscription: Arc<Inscription>,
+    mut lookup: impl FnMut(&InscriptionId) -> anyhow::Result<Arc<Inscription>>,
+) -> anyhow::Result<Arc<Inscription>> {
+    let mut resolved = inscription.clone();
+    for _ in 0..MAX_DELEGATE_DEPTH {
+        let Some(delegate) = &resolved.delegate else {
+            return Ok(resolved);
+        };
+        resolved = lookup(delegate)?;
+    }
+    match &resolved.delegate {
+        Some(_) => Err(anyhow!(
+            "Delegates of {} go more than {MAX_DELEGATE_DEPTH} links deep, or loop",
+            inscription.inscription_id()
+        )),
+        None => Ok(resolved),
+    }
+}
+
+/// Look up the first inscription of a delegate id, for [`resolve_delegate`]
+pub(crate) fn first_inscription(
+    inscriptions: anyhow::Result<Vec<Arc<Inscription>>>,
+    inscription_id: &InscriptionId,
+) -> anyhow::Result<Arc<Inscription>> {
+    inscriptions?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Delegate {inscription_id} not found"))
+}
+
+/// Whether ord binds an inscription to a sat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// Inscribed on a sat of the input holding its envelope
+    Bound,
+    /// The input holding its envelope spends an output without value, so there is no sat to
+    /// inscribe. ord still indexes these, but they can't be transferred.
+    Unbound,
+    /// The spent output couldn't be looked up, such as without a node or `txindex=1`
+    Unknown,
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Binding::Bound => "bound",
+            Binding::Unbound => "unbound",
+            Binding::Unknown => "unknown",
+        })
+    }
+}
+
+/// Work out an inscription's [`Binding`] from the value of the output spent by the input holding
+/// its envelope. Inscriptions that a pointer or fees move elsewhere still have a sat, so only
+/// inputs without value leave them unbound.
+pub(crate) fn binding(client: &dyn ChainSource, inscription: &Inscription) -> Binding {
+    let spent_value = || -> anyhow::Result<u64> {
+        let reveal = client.get_raw_transaction(&inscription.txid, None)?;
+        let outpoint = reveal
+            .input
+            .get(inscription.input)
+            .ok_or_else(|| anyhow!("Input {} not found", inscription.input))?
+            .previous_output;
+        let spent = client.get_raw_transaction(&outpoint.txid, None)?;
+        let output = spent
+            .output
+            .get(outpoint.vout as usize)
+            .ok_or_else(|| anyhow!("Output {outpoint} not found"))?;
+        Ok(output.value.to_sat())
+    };
+    match spent_value() {
+        Ok(0) => Binding::Unbound,
+        Ok(_) => Binding::Bound,
+        Err(_) => Binding::Unknown,
+    }
+}
+
+pub(crate) fn fetch_and_print(
+    args: &crate::args::Args,
+    inscription_id: &InscriptionId,
+) -> anyhow::Result<()> {
+    let inscriptions = fetch(args, inscription_id)?;
+    let mut out = AnsiStrip::new(stdout().lock(), args.strip_ansi());
+    for inscription in inscriptions {
+        let inscription = match &inscription.delegate {
+            Some(delegate) => {
+                writeln!(out, "{}", format!("(delegates to {delegate})").dark_grey())?;
+                resolve_delegate(inscription, |id| first_inscription(fetch(args, id), id))?
+            }
+            None => inscription,
+        };
+        if args.explain() {
+            let (parsed, steps) =
+                explain_data(&inscription.data, &inscription.mime, &args.parse_options());
+            for step in steps {
+                writeln!(out, "{} {step}", "-".dark_grey())?;
+            }
+            writeln!(out, "{} classified as {}", "=".dark_grey(), parsed.kind())?;
+        }
+        if inscription.truncated {
+            writeln!(
+                out,
+                "{} truncated to {} bytes",
+                "Warning:".yellow(),
+                inscription.data.len()
+            )?;
+        }
+        inscription.print(&mut out, &args.print_options())?;
+    }
+    writeln!(out)?;
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Fetch an inscription's content from an ord server's `/content/<INSCRIPTION_ID>` endpoint
+/// instead of the node. Only the content and its `Content-Type` are available this way.
+fn fetch_from_ord(
+    base: &str,
+    inscription_id: &InscriptionId,
+    opts: &ParseOptions,
+) -> anyhow::Result<Inscription> {
+    let url = format!("{}/content/{inscription_id}", base.trim_end_matches('/'));
+    let response = ureq::get(&url).call()?;
+    let mime = response
+        .header("Content-Type")
+        .unwrap_or(UNTYPED_MEDIA_TYPE)
+        .to_string();
+    let mut data = Vec::new();
+    response.into_reader().read_to_end(&mut data)?;
+    let parsed = parse_data(&data, &mime, opts);
+    Ok(Inscription {
+        txid: inscription_id.0,
+        // The server doesn't say which input holds the inscription
+        input: 0,
+        index: inscription_id.1,
+        mime,
+        data,
+        parsed,
+        truncated: false,
+        content_encoding: None,
+        encoded_data: None,
+        metadata: None,
+        metaprotocol: None,
+        parents: Vec::new(),
+        delegate: None,
+        pointer: None,
+        transferred_by: None,
+    })
+}
+
+/// Look up an inscription's media type and content size with a `HEAD` request to an ord server's
+/// `/content/<INSCRIPTION_ID>` endpoint, without downloading the content. The size is unknown if
+/// the server doesn't send a `Content-Length`.
+pub(crate) fn head_from_ord(
+    base: &str,
+    inscription_id: &InscriptionId,
+) -> anyhow::Result<(String, Option<usize>)> {
+    let url = format!("{}/content/{inscription_id}", base.trim_end_matches('/'));
+    let response = ureq::head(&url).call()?;
+    let mime = response
+        .header("Content-Type")
+        .unwrap_or(UNTYPED_MEDIA_TYPE)
+        .to_string();
+    let size = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok());
+    Ok((mime, size))
+}
+
+/// Look up the ids of the inscriptions on a sat through an ord server's `/sat/<SAT>` endpoint,
+/// which accepts both sat names and numbers
+pub(crate) fn sat_inscriptions(base: &str, sat: &str) -> anyhow::Result<Vec<InscriptionId>> {
+    let url = format!("{}/sat/{sat}", base.trim_end_matches('/'));
+    let response = ureq::get(&url).set("Accept", "application/json").call()?;
+    let response: serde_json::Value = serde_json::from_reader(response.into_reader())?;
+    response
+        .get("inscriptions")
+        .and_then(serde_json::Value::as_array)
+        .ok_or_else(|| anyhow!("Unexpected response from {url}"))?
+        .iter()
+        .map(|id| {
+            id.as_str()
+                .ok_or_else(|| anyhow!("Unexpected inscription id {id}"))?
+                .parse()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bitcoin::{
+        absolute::LockTime,
+        opcodes::{all::OP_CHECKSIG, OP_FALSE},
+        transaction::Version,
+        Amount, BlockHash, OutPoint, ScriptBuf, Sequence, TxOut, Witness,
+    };
+
+    use super::*;
+
+    /// Build a tapscript containing one `text/plain` envelope per body
+    pub(crate) fn envelope_script(bodies: &[&[u8]]) -> ScriptBuf {
+        let mut builder = bitcoin::script::Builder::new().push_opcode(OP_CHECKSIG);
+        for body in bodies {
+            builder = builder
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(b"ord")
+                .push_slice([1])
+                .push_slice(b"text/plain")
+                .push_slice([])
+                .push_slice(<&bitcoin::script::PushBytes>::try_from(*body).unwrap())
+                .push_opcode(OP_ENDIF);
+        }
+        builder.into_script()
+    }
+
+    /// Serves the transactions it holds by txid, like a node with `txindex=1`
+    pub(crate) struct MockChain(pub(crate) Vec<Transaction>);
+
+    impl ChainSource for MockChain {
+        fn get_raw_transaction(
+            &self,
+            txid: &Txid,
+            _: Option<&BlockHash>,
+        ) -> anyhow::Result<Transaction> {
+            self.0
+                .iter()
+                .find(|tx| tx.txid() == *txid)
+                .cloned()
+                .ok_or_else(|| anyhow!("No such transaction"))
+        }
+
+        fn get_block_hash(&self, _: u64) -> anyhow::Result<BlockHash> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_block(&self, _: &BlockHash) -> anyhow::Result<bitcoin::Block> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_block_count(&self) -> anyhow::Result<u64> {
+            Err(anyhow!("unused"))
+        }
+
+        fn get_raw_mempool(&self) -> anyhow::Result<Vec<Txid>> {
+            Err(anyhow!("unused"))
+        }
+    }
+
+    /// Build a transaction with a single script path spend revealing `script`
+    pub(crate) fn script_tx(script: &Script) -> Transaction {
+        let mut witness = Witness::new();
+        witness.push([0; 64]);
+        witness.push(script.as_bytes());
+        witness.push([0xc0; 33]);
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness,
+            }],
+            output: Vec::new(),
+        }
+    }
+
+    /// Build a transaction revealing one `text/plain` inscription per body
+    pub(crate) fn inscription_tx(bodies: &[&[u8]]) -> Transaction {
+        script_tx(&envelope_script(bodies))
+    }
+
+    /// A complete, untruncated envelope
+    fn envelope(mime: &str, data: &[u8]) -> Envelope {
+        Envelope {
+            mime: mime.into(),
+            data: data.to_vec(),
+            encoding: None,
+            metadata: Vec::new(),
+            metaprotocol: None,
+            parents: Vec::new(),
+            delegate: None,
+            pointer: None,
+            truncated: false,
+        }
+    }
+
+    /// Deserialize a hex encoded transaction from `tests/fixtures/<name>.hex`
+    pub(crate) fn fixture_tx(name: &str) -> Transaction {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(format!("{name}.hex"));
+        let hex = std::fs::read_to_string(&path).unwrap();
+        bitcoin::consensus::deserialize(&hex::decode(hex.trim()).unwrap()).unwrap()
+    }
+
+    /// Extract every inscription from a fixture, as `(mime, size, kind)`
+    fn extract_fixture(name: &str) -> Vec<(String, usize, &'static str)> {
+        Inscription::extract_all(&fixture_tx(name), &ParseOptions::default())
+            .unwrap()
+            .iter()
+            .map(|i| (i.mime.clone(), i.data.len(), i.parsed.kind()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let text = "text/plain;charset=utf-8".to_string();
+        assert_eq!(extract_fixture("text"), [(text.clone(), 11, "text")]);
+        assert_eq!(
+            extract_fixture("json"),
+            [("application/json".into(), 33, "json")]
+        );
+        assert_eq!(
+            extract_fixture("image"),
+            [("image/png".into(), 120, "image")]
+        );
+
+        let brc20 =
+            Inscription::extract_all(&fixture_tx("brc20"), &ParseOptions::default()).unwrap();
+        assert_eq!(brc20.len(), 1);
+        assert_eq!(brc20[0].mime, text);
+        assert!(brc20[0].parsed.is_brc20());
+        assert!(matches!(
+            brc20[0].parsed.brc20_op(),
+            Some(crate::brc20::Brc20Op::Mint { .. })
+        ));
+
+        assert_eq!(
+            extract_fixture("multi"),
+            [
+                (text, 5, "text"),
+                ("image/png".into(), 120, "image"),
+                ("text/html;charset=utf-8".into(), 31, "html"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_script() {
+        let script = envelope_script(&[b"hello", b"world"]);
+        let tx = script_tx(&script);
+        let opts = ParseOptions::default();
+        let from_script = Inscription::extract_script(&script, tx.txid(), 0, &opts);
+        let from_tx = Inscription::extract_all(&tx, &opts).unwrap();
+        let ids = |inscriptions: &[Arc<Inscription>]| {
+            inscriptions
+                .iter()
+                .map(|i| (i.inscription_id(), i.data.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&from_script), ids(&from_tx));
+        assert!(Inscription::extract_script(&ScriptBuf::new(), tx.txid(), 0, &opts).is_empty());
+    }
+
+    #[test]
+    fn test_normal_inscription() {
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([])
+            .push_slice(b"hello world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let results = extract_script(&script, usize::MAX);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results, [envelope("text/plain", b"hello world")]);
+    }
+
+    #[test]
+    fn test_ignore_preceding() {
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([])
+            .push_slice(b"hello world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let results = extract_script(&script, usize::MAX);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results, [envelope("text/plain", b"hello world")]);
+    }
+
+    #[test]
+    fn test_multiple_inscriptions_per_witness() {
         let script = bitcoin::script::Builder::new()
             .push_opcode(OP_CHECKSIG)
             .push_opcode(OP_FALSE)
@@ -405,14 +1991,784 @@ mod tests {
             .push_slice(b"goodbye world")
             .push_opcode(OP_ENDIF)
             .into_script();
-        let results = extract_script(&script);
+        let results = extract_script(&script, usize::MAX);
         assert_eq!(results.len(), 2);
         assert_eq!(
             results,
             [
-                ("text/plain".into(), b"hello world".to_vec()),
-                ("text/plain".into(), b"goodbye world".to_vec())
+                envelope("text/plain", b"hello world"),
+                envelope("text/plain", b"goodbye world")
             ]
         );
     }
+
+    #[test]
+    fn test_text_threshold() {
+        let opts = ParseOptions::default();
+        assert!(matches!(
+            parse_data(b"hello\nworld", "text/plain", &opts),
+            ParsedData::Text(_)
+        ));
+        assert!(matches!(
+            parse_data(b"\x01\x02\x03\x04a", "text/plain", &opts),
+            ParsedData::Binary
+        ));
+
+        let lenient = ParseOptions {
+            text_threshold: 0.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_data(b"\x01\x02\x03\x04a", "text/plain", &lenient),
+            ParsedData::Text(_)
+        ));
+    }
+
+    #[test]
+    fn test_detected_format() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(1, 1)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let parsed = parse_data(png.get_ref(), "image/png", &ParseOptions::default());
+        assert!(matches!(parsed, ParsedData::Image(_, ImageFormat::Png)));
+        assert_eq!(parsed.detected_format(), Some("png"));
+
+        let parsed = parse_data(b"{}", "application/json", &ParseOptions::default());
+        assert_eq!(parsed.detected_format(), Some("json"));
+
+        let parsed = parse_data(&[0xff, 0x00, 0xfe], "", &ParseOptions::default());
+        assert_eq!(parsed.detected_format(), None);
+    }
+
+    #[test]
+    fn test_explain_data() {
+        let opts = ParseOptions::default();
+        let (parsed, steps) = explain_data(b"{\"a\": 1", "application/json", &opts);
+        assert!(matches!(parsed, ParsedData::Text(_)));
+        assert!(steps[0].contains("application/json"));
+        assert!(steps
+            .iter()
+            .any(|step| step.starts_with("JSON parsing failed")));
+
+        let (parsed, steps) = explain_data(&[0xff, 0x00, 0xfe], "image/png", &opts);
+        assert!(matches!(parsed, ParsedData::Binary));
+        assert!(steps
+            .iter()
+            .any(|step| step.starts_with("UTF-8 decoding failed")));
+        assert_eq!(steps.last().unwrap(), "image format not recognized");
+    }
+
+    #[test]
+    fn test_fit_image() {
+        // A wide white image in a 40x10 cell box, which is 40x20 pixels
+        let wide = DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 10, Rgba([255; 4])));
+        for fit in [Fit::Contain, Fit::Cover, Fit::Stretch] {
+            let fitted = fit_image(&wide, 40, 10, fit);
+            assert_eq!((fitted.width(), fitted.height()), (40, 20), "{fit}");
+        }
+
+        // Contain letterboxes above and below, cover and stretch fill the box
+        let contained = fit_image(&wide, 40, 10, Fit::Contain).to_rgba8();
+        assert_eq!(contained.get_pixel(20, 0), &Rgba([0, 0, 0, 255]));
+        assert_eq!(contained.get_pixel(20, 10), &Rgba([255; 4]));
+        let covered = fit_image(&wide, 40, 10, Fit::Cover).to_rgba8();
+        assert_eq!(covered.get_pixel(20, 0), &Rgba([255; 4]));
+
+        assert_eq!("Cover".parse::<Fit>().unwrap(), Fit::Cover);
+        assert!("squash".parse::<Fit>().is_err());
+    }
+
+    #[test]
+    fn test_gif_frames() {
+        let mut data = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut data);
+            for shade in [0, 255] {
+                let image = RgbaImage::from_pixel(2, 2, Rgba([shade, shade, shade, 255]));
+                encoder.encode_frame(Frame::new(image)).unwrap();
+            }
+        }
+        let parsed = parse_data(&data, "image/gif", &ParseOptions::default());
+        assert!(matches!(parsed, ParsedData::Image(_, ImageFormat::Gif)));
+
+        let frames = gif_frames(&data);
+        assert_eq!(frames.len(), 2);
+        let shade = |frame: Option<&Frame>| frame.unwrap().buffer().get_pixel(0, 0)[0];
+        assert!(chosen_frame(&frames, None).is_none());
+        assert_eq!(shade(chosen_frame(&frames, Some(1))), 0);
+        assert_eq!(shade(chosen_frame(&frames, Some(2))), 255);
+        // Past the end is the last frame
+        assert_eq!(shade(chosen_frame(&frames, Some(9))), 255);
+        assert!(gif_frames(b"GIF89a").is_empty());
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="20"><rect width="10" height="20" fill="red"/></svg>"#;
+        let opts = ParseOptions::default();
+        let parsed = parse_data(svg.as_bytes(), "image/svg+xml", &opts);
+        assert!(matches!(&parsed, ParsedData::Svg(text) if text == svg));
+        assert!(!parsed.is_html());
+        assert_eq!(parsed.detected_format(), Some("svg"));
+        assert!(parse_data(svg.as_bytes(), "image/svg+xml;charset=utf-8", &opts).is_svg());
+        assert!(parse_data(svg.as_bytes(), "text/html", &opts).is_html());
+
+        let image = rasterize_svg(svg).unwrap();
+        assert_eq!((image.width(), image.height()), (256, 512));
+        assert_eq!(
+            image.to_rgba8().get_pixel(128, 256),
+            &image::Rgba([255, 0, 0, 255])
+        );
+        assert!(rasterize_svg("<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_prefer() {
+        let png = &Inscription::extract_all(&fixture_tx("image"), &ParseOptions::default())
+            .unwrap()[0]
+            .data;
+        let html = b"<!DOCTYPE html><p>hi</p>";
+        let kind = |data: &[u8], mime: &str, prefer: Preference| {
+            let opts = ParseOptions {
+                prefer,
+                ..Default::default()
+            };
+            parse_data(data, mime, &opts).kind()
+        };
+        let cases: [(&[u8], &str, [&str; 3]); 4] = [
+            (png, "text/plain", ["image", "text", "image"]),
+            (
+                &[0, 159, 146, 150],
+                "text/plain",
+                ["binary", "text", "binary"],
+            ),
+            (html, "text/plain", ["text", "text", "html"]),
+            (b"hello", "text/html", ["html", "html", "text"]),
+        ];
+        for (data, mime, expected) in cases {
+            let kinds = [Preference::Balanced, Preference::Mime, Preference::Content]
+                .map(|prefer| kind(data, mime, prefer));
+            assert_eq!(kinds, expected, "{mime}");
+        }
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let data = br#"{ "b": [ {"d": 1, "c": 2} ], "a": "x y" }"#;
+        let parsed = parse_data(data, "application/json", &ParseOptions::default());
+        let inscription = Inscription {
+            txid: inscription_tx(&[]).txid(),
+            input: 0,
+            index: 0,
+            mime: "application/json".into(),
+            data: data.to_vec(),
+            parsed,
+            truncated: false,
+            content_encoding: None,
+            encoded_data: None,
+            metadata: None,
+            metaprotocol: None,
+            parents: Vec::new(),
+            delegate: None,
+            pointer: None,
+            transferred_by: None,
+        };
+        assert_eq!(
+            inscription.with_canonical_json().data,
+            br#"{"a":"x y","b":[{"c":2,"d":1}]}"#
+        );
+    }
+
+    #[test]
+    fn test_charset() {
+        assert_eq!(
+            mime_charset("text/plain; Charset=\"latin1\""),
+            Some("latin1")
+        );
+        assert_eq!(mime_charset("text/plain"), None);
+
+        let opts = ParseOptions::default();
+        let latin1 = b"caf\xe9 cr\xe8me";
+        match parse_data(latin1, "text/plain;charset=iso-8859-1", &opts) {
+            ParsedData::Text(text) => assert_eq!(text, "café crème"),
+            _ => panic!("latin1 text was not decoded"),
+        }
+        assert!(matches!(
+            parse_data(latin1, "text/plain;charset=unknown", &opts),
+            ParsedData::Binary
+        ));
+    }
+
+    #[test]
+    fn test_parse_id_line() {
+        let txid = "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799";
+        let single = InscriptionId::parse_line(&format!("{txid}i2")).unwrap();
+        assert_eq!(
+            single.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [format!("{txid}i2")]
+        );
+
+        let range = InscriptionId::parse_line(&format!("{txid}i0-3")).unwrap();
+        assert_eq!(
+            range.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            (0..=3).map(|i| format!("{txid}i{i}")).collect::<Vec<_>>()
+        );
+
+        assert!(InscriptionId::parse_line(&format!("{txid}i3-0")).is_err());
+        assert!(InscriptionId::parse_line(&format!("{txid}i0-")).is_err());
+        assert_eq!(
+            InscriptionId::parse_line(&format!("{txid}i0-9999"))
+                .unwrap()
+                .len(),
+            MAX_ID_RANGE
+        );
+        assert!(InscriptionId::parse_line(&format!("{txid}i0-10000")).is_err());
+        assert!(InscriptionId::parse_line(&format!("{txid}i0-{}", usize::MAX)).is_err());
+        assert!(InscriptionId::parse_line("hello world").is_err());
+    }
+
+    #[test]
+    fn test_fetch_id_range() {
+        // A batch reveal, with every envelope in its only input
+        let tx = inscription_tx(&[b"zero", b"one", b"two", b"three"]);
+        let chain = MockChain(vec![tx.clone()]);
+        let opts = ParseOptions::default();
+        let fetched: Vec<_> = InscriptionId::parse_line(&format!("{}i1-3", tx.txid()))
+            .unwrap()
+            .iter()
+            .map(|id| fetch_from_node(&chain, id, &opts).unwrap().remove(0))
+            .collect();
+        let fetched: Vec<_> = fetched
+            .iter()
+            .map(|i| (i.index, i.data.as_slice()))
+            .collect();
+        assert_eq!(
+            fetched,
+            [(1, &b"one"[..]), (2, &b"two"[..]), (3, &b"three"[..])]
+        );
+    }
+
+    #[test]
+    fn test_untyped_inscriptions() {
+        let with_separator = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([])
+            .push_slice(b"hello world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let without_separator = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice(b"hello ")
+            .push_slice(b"world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        for script in [with_separator, without_separator] {
+            assert_eq!(
+                extract_script(&script, usize::MAX),
+                [envelope(UNTYPED_MEDIA_TYPE, b"hello world")]
+            );
+        }
+
+        let empty = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(
+            extract_script(&empty, usize::MAX),
+            [envelope(UNTYPED_MEDIA_TYPE, b"")]
+        );
+
+        // Fields without a content type are still read as fields, not as the body
+        let fields_only = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([POINTER_TAG])
+            .push_slice([0x22, 0x02])
+            .push_slice([METAPROTOCOL_TAG])
+            .push_slice(b"sns")
+            .push_slice([])
+            .push_slice(b"hello world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(
+            extract_script(&fields_only, usize::MAX),
+            [Envelope {
+                metaprotocol: Some("sns".into()),
+                pointer: Some(546),
+                ..envelope(UNTYPED_MEDIA_TYPE, b"hello world")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_taproot_only() {
+        let taproot_only = ParseOptions {
+            taproot_only: true,
+            ..Default::default()
+        };
+        let taproot = inscription_tx(&[b"hello world"]);
+        assert_eq!(
+            Inscription::extract_all(&taproot, &taproot_only)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // A legacy spend with no witness at all
+        let mut legacy = taproot.clone();
+        legacy.input[0].witness = Witness::new();
+        assert!(!is_script_path_spend(&legacy.input[0]));
+        assert!(Inscription::extract_all(&legacy, &taproot_only)
+            .unwrap()
+            .is_empty());
+
+        // A segwit v0 style witness carrying an envelope where a tapscript would be, which is
+        // only parsed when not restricted to taproot
+        let mut segwit = taproot.clone();
+        let mut witness = Witness::new();
+        witness.push(envelope_script(&[b"hello world"]).as_bytes());
+        witness.push([0x21; 34]);
+        segwit.input[0].witness = witness;
+        assert!(!is_script_path_spend(&segwit.input[0]));
+        assert!(Inscription::extract_all(&segwit, &taproot_only)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            Inscription::extract_all(&segwit, &ParseOptions::default())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_max_inscription_bytes() {
+        let mut builder = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([])
+            .push_slice(b"hello ");
+        for _ in 0..1000 {
+            builder = builder.push_slice(b"world");
+        }
+        let script = builder.push_opcode(OP_ENDIF).into_script();
+        assert_eq!(
+            extract_script(&script, 8),
+            [Envelope {
+                mime: UNTYPED_MEDIA_TYPE.into(),
+                data: b"hello wo".to_vec(),
+                encoding: None,
+                metadata: Vec::new(),
+                metaprotocol: None,
+                parents: Vec::new(),
+                delegate: None,
+                pointer: None,
+                truncated: true,
+            }]
+        );
+        assert!(!extract_script(&script, 5006)[0].truncated);
+    }
+
+    #[test]
+    fn test_gzip_content_encoding() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(b"hello world").unwrap();
+        let compressed = encoder.finish().unwrap();
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([CONTENT_ENCODING_TAG])
+            .push_slice(b"gzip")
+            .push_slice([])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(compressed.as_slice()).unwrap())
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        let inscriptions =
+            Inscription::extract_all(&script_tx(&script), &ParseOptions::default()).unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        let inscription = &inscriptions[0];
+        assert_eq!(inscription.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(inscription.data, b"hello world");
+        assert_eq!(inscription.encoded_data.as_ref(), Some(&compressed));
+        assert_eq!(inscription.encoding(), "gzip");
+        assert_eq!(inscription.encoded_size(), compressed.len());
+        assert!(matches!(&inscription.parsed, ParsedData::Text(text) if text == "hello world"));
+
+        assert_eq!(
+            decode_content(&compressed, "gzip", 5).unwrap(),
+            (b"hello".to_vec(), true)
+        );
+        assert!(decode_content(&compressed, "br", 1024).is_err());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let mut cbor = Vec::new();
+        ciborium::into_writer(
+            &serde_json::json!({"name": "Ortty #1", "traits": ["blue"]}),
+            &mut cbor,
+        )
+        .unwrap();
+        let (first, second) = cbor.split_at(10);
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([METADATA_TAG])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(first).unwrap())
+            .push_slice([METADATA_TAG])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(second).unwrap())
+            .push_slice([])
+            .push_slice(b"hello world")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let inscriptions =
+            Inscription::extract_all(&script_tx(&script), &ParseOptions::default()).unwrap();
+        assert_eq!(
+            inscriptions[0].metadata,
+            Some(serde_json::json!({"name": "Ortty #1", "traits": ["blue"]}))
+        );
+        assert_eq!(inscriptions[0].data, b"hello world");
+
+        assert_eq!(decode_metadata(&[]), None);
+        assert_eq!(decode_metadata(&cbor[..5]), None);
+        assert_eq!(decode_metadata(&[0xff, 0x00]), None);
+    }
+
+    #[test]
+    fn test_parents() {
+        let parent: InscriptionId =
+            "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0"
+                .parse()
+                .unwrap();
+        let second: InscriptionId =
+            "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i300"
+                .parse()
+                .unwrap();
+        let encode = |id: &InscriptionId, index: &[u8]| {
+            let mut value = id.txid().to_byte_array().to_vec();
+            value.extend(index);
+            value
+        };
+        let (first_value, second_value) = (encode(&parent, &[]), encode(&second, &[0x2c, 0x01]));
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([PARENT_TAG])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(first_value.as_slice()).unwrap())
+            .push_slice([PARENT_TAG])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(second_value.as_slice()).unwrap())
+            .push_slice([])
+            .push_slice(b"child")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let inscriptions =
+            Inscription::extract_all(&script_tx(&script), &ParseOptions::default()).unwrap();
+        assert_eq!(inscriptions[0].parents, [parent, second]);
+        assert_eq!(InscriptionId::from_envelope_value(&[0; 31]), None);
+    }
+
+    #[test]
+    fn test_pointer() {
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([POINTER_TAG])
+            .push_slice([0x10, 0x27])
+            .push_slice([POINTER_TAG])
+            .push_slice([0x01])
+            .push_slice([])
+            .push_slice(b"hello")
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let inscriptions =
+            Inscription::extract_all(&script_tx(&script), &ParseOptions::default()).unwrap();
+        assert_eq!(inscriptions[0].pointer, Some(10000));
+        assert_eq!(inscriptions[0].data, b"hello");
+
+        assert_eq!(decode_pointer(&[]), Some(0));
+        assert_eq!(decode_pointer(&[1, 0, 0, 0, 0, 0, 0, 0, 0]), Some(1));
+        assert_eq!(decode_pointer(&[0, 0, 0, 0, 0, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn test_delegate_envelopes() {
+        let delegate: InscriptionId =
+            "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i1"
+                .parse()
+                .unwrap();
+        let mut value = delegate.txid().to_byte_array().to_vec();
+        value.push(1);
+        let value = <&bitcoin::script::PushBytes>::try_from(value.as_slice()).unwrap();
+        let ord = || {
+            bitcoin::script::Builder::new()
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(b"ord")
+        };
+
+        // Only a delegate, as ord writes them, with no content type or body
+        let bare = ord()
+            .push_slice([DELEGATE_TAG])
+            .push_slice(value)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        // A content type but no body separator
+        let typed = ord()
+            .push_slice([CONTENT_TYPE_TAG])
+            .push_slice(b"image/png")
+            .push_slice([DELEGATE_TAG])
+            .push_slice(value)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+
+        for (script, mime) in [(bare, UNTYPED_MEDIA_TYPE), (typed, "image/png")] {
+            let inscriptions =
+                Inscription::extract_all(&script_tx(&script), &ParseOptions::default()).unwrap();
+            assert_eq!(inscriptions.len(), 1);
+            assert_eq!(inscriptions[0].delegate, Some(delegate.clone()));
+            assert_eq!(inscriptions[0].mime, mime);
+            assert!(inscriptions[0].data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_resolve_delegate() {
+        let id = |index: usize| InscriptionId(inscription_tx(&[]).txid(), index);
+        let inscription = |index: usize, delegate: Option<usize>| {
+            let mut inscription = Inscription::extract_all(
+                &inscription_tx(&[format!("body {index}").as_bytes()]),
+                &ParseOptions::default(),
+            )
+            .unwrap()[0]
+                .as_ref()
+                .clone();
+            inscription.input = index;
+            inscription.index = index;
+            inscription.delegate = delegate.map(id);
+            Arc::new(inscription)
+        };
+
+        // 0 delegates to 1, which delegates to 2
+        let chain = [
+            inscription(0, Some(1)),
+            inscription(1, Some(2)),
+            inscription(2, None),
+        ];
+        let lookup = |id: &InscriptionId| Ok(chain[id.index()].clone());
+        let resolved = resolve_delegate(chain[0].clone(), lookup).unwrap();
+        assert_eq!(resolved.inscription_id(), id(2).to_string());
+        assert_eq!(resolved.data, b"body 2");
+
+        let plain = inscription(3, None);
+        let resolved = resolve_delegate(plain.clone(), |_| unreachable!()).unwrap();
+        assert!(Arc::ptr_eq(&resolved, &plain));
+
+        // A loop between 0 and 1
+        let looped = [inscription(0, Some(1)), inscription(1, Some(0))];
+        assert!(resolve_delegate(looped[0].clone(), |id| Ok(looped[id.index()].clone())).is_err());
+    }
+
+    #[test]
+    fn test_binding() {
+        let funding = |sats: u64| Transaction {
+            output: vec![TxOut {
+                value: Amount::from_sat(sats),
+                script_pubkey: ScriptBuf::new(),
+            }],
+            ..inscription_tx(&[format!("{sats}").as_bytes()])
+        };
+        let reveal = |funding: &Transaction| {
+            let mut tx = inscription_tx(&[b"hello"]);
+            tx.input[0].previous_output = OutPoint::new(funding.txid(), 0);
+            tx
+        };
+        let opts = ParseOptions::default();
+        for (sats, expected) in [(0, Binding::Unbound), (546, Binding::Bound)] {
+            let funding = funding(sats);
+            let reveal = reveal(&funding);
+            let inscription = &Inscription::extract_all(&reveal, &opts).unwrap()[0];
+            let chain = MockChain(vec![funding, reveal]);
+            assert_eq!(binding(&chain, inscription), expected);
+        }
+
+        // Without the spent transaction
+        let reveal = reveal(&funding(0));
+        let inscription = &Inscription::extract_all(&reveal, &opts).unwrap()[0];
+        let chain = MockChain(vec![reveal]);
+        assert_eq!(binding(&chain, inscription), Binding::Unknown);
+    }
+
+    #[test]
+    fn test_index_across_inputs() {
+        let mut tx = inscription_tx(&[b"first"]);
+        tx.input
+            .push(inscription_tx(&[b"second", b"third"]).input.remove(0));
+        let inscriptions = Inscription::extract_all(&tx, &ParseOptions::default()).unwrap();
+        let indices: Vec<_> = inscriptions.iter().map(|i| (i.input, i.index)).collect();
+        assert_eq!(indices, [(0, 0), (1, 1), (1, 2)]);
+        assert_eq!(inscriptions[1].inscription_id(), format!("{}i1", tx.txid()));
+    }
+
+    #[test]
+    fn test_fetch_from_ord_id() {
+        let id = InscriptionId(inscription_tx(&[]).txid(), 5);
+        let (base, server) = crate::chain::tests::serve_once(b"hello".to_vec());
+        let inscription = fetch_from_ord(&base, &id, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            format!("GET /content/{id} HTTP/1.1")
+        );
+        assert_eq!(inscription.inscription_id(), id.to_string());
+        assert_eq!(inscription.data, b"hello");
+    }
+
+    #[test]
+    fn test_fetch_by_index() {
+        let mut tx = inscription_tx(&[b"first"]);
+        tx.input
+            .push(inscription_tx(&[b"second", b"third"]).input.remove(0));
+        let chain = MockChain(vec![tx.clone()]);
+        let opts = ParseOptions::default();
+
+        // The id `scan` prints for the second inscription finds it again
+        let scanned = &Inscription::extract_all(&tx, &opts).unwrap()[1];
+        let id: InscriptionId = scanned.inscription_id().parse().unwrap();
+        let fetched = fetch_from_node(&chain, &id, &opts).unwrap();
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].inscription_id(), scanned.inscription_id());
+        assert_eq!(fetched[0].data, b"second");
+        assert_eq!(fetched[0].input, 1);
+
+        let third = fetch_from_node(&chain, &InscriptionId(tx.txid(), 2), &opts).unwrap();
+        assert_eq!(third[0].data, b"third");
+        assert!(fetch_from_node(&chain, &InscriptionId(tx.txid(), 3), &opts).is_err());
+    }
+
+    #[test]
+    fn test_large_non_inscription_script() {
+        let mut builder = bitcoin::script::Builder::new();
+        for _ in 0..50_000 {
+            builder = builder
+                .push_opcode(OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice(b"not an inscription")
+                .push_opcode(OP_ENDIF)
+                .push_opcode(OP_CHECKSIG);
+        }
+        let script = builder.into_script();
+        assert!(extract_script(&script, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let dump = |data: &[u8], width| {
+            let mut out = Vec::new();
+            hexdump(&mut AnsiStrip::new(&mut out, true), data, width).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            dump(b"hello, world!\n\x00\xffmore", 16),
+            "00000000: 6865 6c6c 6f2c 2077 6f72 6c64 210a 00ff  hello, world!...\n\
+             00000010: 6d6f 7265                                more\n"
+        );
+        assert_eq!(
+            dump(b"0123456789", 8),
+            "00000000: 3031 3233 3435 3637  01234567\n\
+             00000008: 3839                 89\n"
+        );
+        assert_eq!(dump(b"", 16), "");
+    }
+
+    #[test]
+    fn test_print_brc20() {
+        let summary = |json: &str| {
+            let inscription = &Inscription::extract_all(
+                &inscription_tx(&[json.as_bytes()]),
+                &ParseOptions::default(),
+            )
+            .unwrap()[0];
+            let opts = PrintOptions {
+                raw: true,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            inscription
+                .print(&mut AnsiStrip::new(&mut out, true), &opts)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            summary(r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#),
+            "BRC-20 deploy: ordi max=21000000 lim=1000\n"
+        );
+        assert_eq!(
+            summary(r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000"}"#),
+            "BRC-20 deploy: ordi max=21000000\n"
+        );
+        assert_eq!(
+            summary(r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#),
+            "BRC-20 mint: ordi amt=1000\n"
+        );
+        assert_eq!(
+            summary(r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"0.5"}"#),
+            "BRC-20 transfer: ordi amt=0.5\n"
+        );
+        // Unknown ops are printed as plain JSON
+        assert_eq!(
+            summary(r#"{"p":"brc-20","op":"burn","tick":"ordi"}"#),
+            "{\"op\":\"burn\",\"p\":\"brc-20\",\"tick\":\"ordi\"}\n"
+        );
+
+        // The JSON follows the summary unless printing raw
+        let inscription = &Inscription::extract_all(
+            &inscription_tx(&[br#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1"}"#]),
+            &ParseOptions::default(),
+        )
+        .unwrap()[0];
+        let mut out = Vec::new();
+        inscription
+            .print(
+                &mut AnsiStrip::new(&mut out, true),
+                &PrintOptions::default(),
+            )
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("BRC-20 mint: ordi amt=1\n{\n"));
+    }
+
+    #[test]
+    fn test_broken_envelope_terminates() {
+        let script = bitcoin::script::Builder::new()
+            .push_opcode(OP_FALSE)
+            .push_opcode(OP_IF)
+            .push_slice(b"ord")
+            .push_slice([1])
+            .push_slice(b"text/plain")
+            .push_slice([])
+            .push_slice(b"missing endif")
+            .into_script();
+        assert!(extract_script(&script, usize::MAX).is_empty());
+    }
 }
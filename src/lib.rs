@@ -0,0 +1,59 @@
+//! Find and parse Ordinals inscriptions in Bitcoin transactions.
+//!
+//! Parsing needs nothing but the transaction, so it works on transactions from any source and
+//! makes no RPC calls:
+//!
+//! ```
+//! use bitcoin::{consensus::deserialize, Transaction};
+//! use ortty::{Filter, Inscription, ParseOptions, ParsedData};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! # let hex = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/text.hex"));
+//! // A raw transaction, from a node, a block explorer or a file
+//! let raw = hex::decode(hex.trim())?;
+//! let tx: Transaction = deserialize(&raw)?;
+//! for inscription in Inscription::extract_all(&tx, &ParseOptions::default())? {
+//!     println!("{} is {}", inscription.inscription_id(), inscription.mime);
+//!     if let ParsedData::Text(text) = &inscription.parsed {
+//!         println!("{text}");
+//!     }
+//!     assert!(Filter::Text.inscription(&inscription));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `ortty` binary is a thin wrapper around [`cli::main`], which is not part of the stable
+//! API.
+
+mod ansi;
+mod archive;
+mod args;
+mod audio;
+mod brc20;
+mod chain;
+mod collection;
+mod disasm;
+mod doctor;
+mod explore;
+mod filter;
+mod formats;
+mod hash;
+mod highlight;
+mod imagehash;
+mod info;
+mod inscription;
+mod output;
+mod pdf;
+mod recursion;
+mod scan;
+mod theme;
+mod transcript;
+
+#[doc(hidden)]
+pub mod cli;
+
+pub use audio::AudioFormat;
+pub use filter::Filter;
+pub use inscription::{Inscription, InscriptionId, ParseOptions, ParsedData, Preference};
+pub use scan::{extract_block_inscriptions, BlockInscriptions, InscriptionScanner};
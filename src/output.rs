@@ -0,0 +1,481 @@
+use std::{
+    io::{stdout, ErrorKind, StdoutLock, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use bitcoin::{
+    base64::{engine::general_purpose::STANDARD, Engine},
+    Txid,
+};
+use crossterm::style::Stylize;
+use serde_json::json;
+
+use crate::{
+    ansi::AnsiStrip,
+    archive::TarArchive,
+    args::{Args, OutputFormat},
+    imagehash::ImageHash,
+    inscription::{Inscription, ParsedData},
+    scan::BlockSummary,
+};
+
+/// Writes scan results one inscription at a time, either printing, extracting or archiving them
+/// depending on the command line options.
+pub struct Output<'a> {
+    args: &'a Args,
+    out: AnsiStrip<StdoutLock<'static>>,
+    archive: Option<TarArchive>,
+
+    /// Number of extracted images that decoded successfully, and that failed to, when verifying
+    verified: (usize, usize),
+
+    /// Inscriptions collected for `--format json`, printed as one array when finished
+    records: Vec<serde_json::Value>,
+
+    /// Entries for the `manifest.json` of the extract folder, written when finished
+    manifest: Vec<serde_json::Value>,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(args: &'a Args) -> anyhow::Result<Self> {
+        Ok(Output {
+            args,
+            out: AnsiStrip::new(stdout().lock(), args.strip_ansi()),
+            archive: args
+                .extract_tar()
+                .map(|path| TarArchive::create(path, args.content_hash()))
+                .transpose()?,
+            verified: (0, 0),
+            records: Vec::new(),
+            manifest: Vec::new(),
+        })
+    }
+
+    pub fn emit(&mut self, inscription: &Inscription) -> anyhow::Result<()> {
+        let args = self.args;
+        let out = &mut self.out;
+        if let Some(true) = args.web() {
+            inscription.open_web(&args.explorer_url)?;
+        }
+
+        let canonical;
+        let inscription = if args.canonicalize_json() && inscription.parsed.is_json() {
+            canonical = inscription.with_canonical_json();
+            &canonical
+        } else {
+            inscription
+        };
+
+        if args.format() == OutputFormat::Nostr {
+            if let Some(event) = nostr_event(inscription, unix_time()) {
+                writeln!(out, "{event}")?;
+            }
+            out.flush()?;
+            return Ok(());
+        }
+        if args.format() == OutputFormat::Json {
+            self.records.push(json_record(inscription));
+            return Ok(());
+        }
+
+        if inscription.truncated {
+            writeln!(
+                out,
+                "{} {} was truncated to {} bytes",
+                "Warning:".yellow(),
+                inscription.inscription_id(),
+                inscription.data.len()
+            )?;
+        }
+
+        if let Some(archive) = self.archive.as_mut() {
+            writeln!(out, "Archiving {}...", inscription.file_name())?;
+            archive.append(inscription)?;
+        }
+
+        if let Some(extract) = args.extract() {
+            let dir = if args.organize() {
+                extract.join(inscription.parsed.extract_dir())
+            } else {
+                extract.clone()
+            };
+            let original = args.keep_encoding() && inscription.encoded_data.is_some();
+            let mut file_name = inscription.file_name();
+            if original {
+                file_name.push_str(".gz");
+            }
+            let path = dir.join(file_name);
+            writeln!(out, "Writing {}...", path.to_str().unwrap_or_default())?;
+            inscription.write_to_file(&path, original)?;
+            self.manifest
+                .push(manifest_entry(inscription, extract, &path));
+            if args.verify_extract() && !original && is_image(inscription) {
+                match verify_image(&path) {
+                    Ok(()) => {
+                        self.verified.0 += 1;
+                        writeln!(out, "Verified {}", path.display())?;
+                    }
+                    Err(e) => {
+                        self.verified.1 += 1;
+                        writeln!(
+                            out,
+                            "{} {} is not a valid image: {e}",
+                            "Warning:".yellow(),
+                            path.display()
+                        )?;
+                    }
+                }
+            }
+        } else if self.archive.is_none() {
+            if args.print_vout() {
+                writeln!(out, "{}:", inscription.source().cyan())?;
+            }
+            if args.inscription_id().unwrap_or_default() {
+                writeln!(out, "{}:", inscription.inscription_id().yellow())?;
+            }
+            if let Some(txid) = inscription.transferred_by {
+                writeln!(out, "{}", format!("(transferred by {txid})").dark_grey())?;
+            }
+            if let Some(algo) = args.content_hash() {
+                writeln!(out, "{algo}: {}", algo.digest(&inscription.data))?;
+            }
+            if let (true, ParsedData::Image(image, _)) = (args.image_hash(), &inscription.parsed) {
+                writeln!(out, "image hash: {}", ImageHash::new(image))?;
+            }
+            inscription.print(out, &args.print_options())?;
+            writeln!(out)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Print the height, hash, timestamp and transaction count of a block, before its
+    /// inscriptions
+    pub fn block_header(&mut self, block: &BlockSummary) -> anyhow::Result<()> {
+        // Each line of other formats is a record, so there are no headers
+        if self.args.format() != OutputFormat::Text {
+            return Ok(());
+        }
+        let title = match block.height {
+            Some(height) => format!("Block {height}:"),
+            None => format!("Block {}:", block.hash),
+        };
+        writeln!(self.out, "{}", title.magenta().bold())?;
+        let details = format!(
+            "hash {}, mined {}, {} transactions",
+            block.hash,
+            format_timestamp(block.time.into()),
+            block.tx_count
+        );
+        writeln!(self.out, "{}", details.dark_grey())?;
+        Ok(())
+    }
+
+    /// Print the id of a transaction, before its inscriptions
+    pub fn tx_header(&mut self, txid: &Txid) -> anyhow::Result<()> {
+        if self.args.format() != OutputFormat::Text {
+            return Ok(());
+        }
+        writeln!(
+            self.out,
+            "{}",
+            format!("Transaction {txid}:").magenta().bold()
+        )?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        if self.args.format() == OutputFormat::Json {
+            serde_json::to_writer_pretty(&mut self.out, &self.records)?;
+            writeln!(self.out)?;
+            self.out.flush()?;
+        }
+        if let Some(archive) = self.archive {
+            archive.finish()?;
+        }
+        if let (Some(extract), false) = (self.args.extract(), self.manifest.is_empty()) {
+            write_manifest(extract, self.manifest)?;
+        }
+        if self.args.verify_extract() {
+            let (passed, failed) = self.verified;
+            writeln!(self.out, "Verified {passed} images, {failed} failed")?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe an extracted inscription in the extract folder's `manifest.json`. `file` is relative
+/// to the folder, since extensions are only guessed and `--organize` moves files into
+/// subfolders.
+fn manifest_entry(inscription: &Inscription, extract: &Path, path: &Path) -> serde_json::Value {
+    let file = path.strip_prefix(extract).unwrap_or(path);
+    json!({
+        "inscription_id": inscription.inscription_id(),
+        "txid": inscription.txid,
+        "index": inscription.index,
+        "mime": inscription.mime,
+        "content_encoding": inscription.content_encoding,
+        "file": file.to_string_lossy().replace('\\', "/"),
+    })
+}
+
+/// Write the entries to `manifest.json` in the extract folder. Entries already in the manifest
+/// are kept, except those for a file that was extracted again, so repeated extractions into one
+/// folder build up a single manifest.
+fn write_manifest(extract: &Path, entries: Vec<serde_json::Value>) -> anyhow::Result<()> {
+    let path = extract.join("manifest.json");
+    let mut manifest: Vec<serde_json::Value> = match std::fs::read(&path) {
+        Ok(existing) => serde_json::from_slice(&existing)
+            .map_err(|e| anyhow!("Unable to read {}: {e}", path.display()))?,
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    manifest.retain(|old| !entries.iter().any(|new| new["file"] == old["file"]));
+    manifest.extend(entries);
+    std::fs::create_dir_all(extract)?;
+    std::fs::write(&path, serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(())
+}
+
+/// Wrap a text inscription in an unsigned Nostr event (NIP-01) of kind 1, with the content as
+/// is and these tags:
+///
+/// - `["i", "ord:<INSCRIPTION_ID>"]`, identifying the inscription
+/// - `["m", "<MIME>"]`, the declared media type
+///
+/// `created_at` is the time of output. There is no `id`, `pubkey` or `sig`, as the event is not
+/// signed. Binary and image content has no event.
+pub fn nostr_event(inscription: &Inscription, created_at: u64) -> Option<serde_json::Value> {
+    let content = match &inscription.parsed {
+        ParsedData::Text(text) | ParsedData::Html(text) => text.clone(),
+        ParsedData::Json(value) => value.to_string(),
+        ParsedData::Audio(_)
+        | ParsedData::Binary
+        | ParsedData::Image(..)
+        | ParsedData::Pdf
+        | ParsedData::Svg(_) => return None,
+    };
+    Some(json!({
+        "kind": 1,
+        "created_at": created_at,
+        "tags": [
+            ["i", format!("ord:{}", inscription.inscription_id())],
+            ["m", inscription.mime],
+        ],
+        "content": content,
+    }))
+}
+
+/// Describe an inscription for `--format json`, with its content in base64 and, for text and
+/// JSON content, decoded as well:
+///
+/// - `text`, the content of text, HTML and SVG inscriptions
+/// - `json`, the parsed content of JSON inscriptions
+pub fn json_record(inscription: &Inscription) -> serde_json::Value {
+    let mut record = json!({
+        "inscription_id": inscription.inscription_id(),
+        "txid": inscription.txid,
+        "index": inscription.index,
+        "mime": inscription.mime,
+        "size": inscription.data.len(),
+        "encoding": inscription.encoding(),
+        "encoded_size": inscription.encoded_size(),
+        "data": STANDARD.encode(&inscription.data),
+    });
+    match &inscription.parsed {
+        ParsedData::Text(text) | ParsedData::Html(text) | ParsedData::Svg(text) => {
+            record["text"] = text.as_str().into()
+        }
+        ParsedData::Json(value) => record["json"] = value.clone(),
+        ParsedData::Audio(_) | ParsedData::Binary | ParsedData::Image(..) | ParsedData::Pdf => {}
+    }
+    record
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Format a Unix timestamp as a UTC date and time, such as `2023-07-24 03:17:36 UTC`
+pub fn format_timestamp(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86_400, timestamp % 86_400);
+    // Convert days since the epoch to a civil date, counting eras of 400 years from 0000-03-01
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Whether an inscription is, or claims to be, a raster image that can be decoded. SVG is text
+/// and is skipped
+fn is_image(inscription: &Inscription) -> bool {
+    let mime = inscription.mime.to_lowercase();
+    inscription.parsed.is_image() || (mime.starts_with("image/") && !mime.contains("svg"))
+}
+
+/// Read an extracted image back from disk and check that it decodes
+fn verify_image(path: &Path) -> anyhow::Result<()> {
+    image::load_from_memory(&std::fs::read(path)?)?;
+    Ok(())
+}
+
+/// Format a size in bytes using binary units, such as `47.0 KiB`, or as plain bytes when `raw`
+/// is set.
+pub fn format_size(bytes: usize, raw: bool) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if raw || bytes < 1024 {
+        return format!("{bytes} bytes");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Format a number with a comma between each group of three digits, such as `1,234,567`
+pub fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::tests::inscription_tx;
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1000), "1,000");
+        assert_eq!(group_digits(1234567), "1,234,567");
+        assert_eq!(group_digits(123456), "123,456");
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512, false), "512 bytes");
+        assert_eq!(format_size(48128, false), "47.0 KiB");
+        assert_eq!(format_size(48128, true), "48128 bytes");
+        assert_eq!(format_size(3 * 1024 * 1024 + 512 * 1024, false), "3.5 MiB");
+    }
+
+    #[test]
+    fn test_nostr_event() {
+        let inscription = Inscription::extract_all(&inscription_tx(&[b"gm"]), &Default::default())
+            .unwrap()
+            .remove(0);
+        let event = nostr_event(&inscription, 1_700_000_000).unwrap();
+        assert_eq!(
+            event,
+            json!({
+                "kind": 1,
+                "created_at": 1_700_000_000,
+                "tags": [
+                    ["i", format!("ord:{}", inscription.inscription_id())],
+                    ["m", "text/plain"],
+                ],
+                "content": "gm",
+            })
+        );
+
+        let binary =
+            Inscription::extract_all(&inscription_tx(&[&[0, 159, 146, 150]]), &Default::default())
+                .unwrap()
+                .remove(0);
+        assert_eq!(nostr_event(&binary, 0), None);
+    }
+
+    #[test]
+    fn test_json_record() {
+        let tx = inscription_tx(&[b"{\"a\":1}", &[0, 159, 146, 150]]);
+        let inscriptions = Inscription::extract_all(&tx, &Default::default()).unwrap();
+        assert_eq!(
+            json_record(&inscriptions[0]),
+            json!({
+                "inscription_id": inscriptions[0].inscription_id(),
+                "txid": tx.txid(),
+                "index": 0,
+                "mime": "text/plain",
+                "size": 7,
+                "encoding": "identity",
+                "encoded_size": 7,
+                "data": "eyJhIjoxfQ==",
+                "json": {"a": 1},
+            })
+        );
+
+        let binary = json_record(&inscriptions[1]);
+        assert_eq!(binary["index"], 1);
+        assert_eq!(binary["data"], "AJ+Slg==");
+        assert!(binary.get("text").is_none() && binary.get("json").is_none());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let dir = std::env::temp_dir().join(format!("ortty-manifest-{}", std::process::id()));
+        let tx = inscription_tx(&[b"gm", b"gn"]);
+        let inscriptions = Inscription::extract_all(&tx, &Default::default()).unwrap();
+        let entry = |idx: usize| {
+            let path = dir.join("text").join(inscriptions[idx].file_name());
+            manifest_entry(&inscriptions[idx], &dir, &path)
+        };
+        assert_eq!(
+            entry(0),
+            json!({
+                "inscription_id": inscriptions[0].inscription_id(),
+                "txid": tx.txid(),
+                "index": 0,
+                "mime": "text/plain",
+                "content_encoding": null,
+                "file": format!("text/{}", inscriptions[0].file_name()),
+            })
+        );
+
+        // A second extraction adds to the manifest, replacing entries for the same file
+        write_manifest(&dir, vec![entry(0)]).unwrap();
+        write_manifest(&dir, vec![entry(1), entry(0)]).unwrap();
+        let manifest: Vec<serde_json::Value> =
+            serde_json::from_slice(&std::fs::read(dir.join("manifest.json")).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(manifest, [entry(1), entry(0)]);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(1_231_006_505), "2009-01-03 18:15:05 UTC");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29 23:59:59 UTC");
+    }
+}
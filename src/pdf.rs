@@ -0,0 +1,131 @@
+use lopdf::{decode_text_string, Document};
+
+/// Magic bytes at the start of every PDF document
+pub const PDF_MAGIC: &[u8] = b"%PDF-";
+
+/// Page count and document information of a PDF, shown when its pages can't be rendered
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PdfSummary {
+    pub pages: usize,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl PdfSummary {
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        let document = Document::load_mem(data)?;
+        let info = document
+            .trailer
+            .get_deref(b"Info", &document)
+            .and_then(|info| info.as_dict())
+            .ok();
+        let field = |key: &[u8]| {
+            info.and_then(|info| info.get_deref(key, &document).ok())
+                .and_then(|value| decode_text_string(value).ok())
+                .filter(|value| !value.trim().is_empty())
+        };
+        Ok(PdfSummary {
+            pages: document.get_pages().len(),
+            title: field(b"Title"),
+            author: field(b"Author"),
+        })
+    }
+}
+
+/// Width of a rendered page, in pixels
+#[cfg(feature = "pdf-render")]
+const RENDER_WIDTH: i32 = 512;
+
+/// Render the first page of a PDF with the system's pdfium library
+#[cfg(feature = "pdf-render")]
+pub fn render_first_page(data: &[u8]) -> anyhow::Result<image::DynamicImage> {
+    use anyhow::anyhow;
+    use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+
+    let bindings =
+        Pdfium::bind_to_system_library().map_err(|e| anyhow!("pdfium library not found: {e}"))?;
+    let pdfium = Pdfium::new(bindings);
+    let document = pdfium.load_pdf_from_byte_slice(data, None)?;
+    let page = document.pages().first()?;
+    let bitmap = page.render_with_config(&PdfRenderConfig::new().set_target_width(RENDER_WIDTH))?;
+    let image = image::RgbaImage::from_raw(
+        bitmap.width() as u32,
+        bitmap.height() as u32,
+        bitmap.as_rgba_bytes(),
+    )
+    .ok_or_else(|| anyhow!("Rendered page has the wrong size"))?;
+    Ok(image::DynamicImage::ImageRgba8(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{dictionary, Object, Stream, StringFormat};
+
+    use super::*;
+    use crate::inscription::{parse_data, ParseOptions};
+
+    /// A PDF with `pages` blank pages and a title
+    fn pdf(pages: usize, title: &str) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..pages)
+            .map(|_| {
+                let content = document.add_object(Stream::new(dictionary! {}, Vec::new()));
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "Contents" => content,
+                    })
+                    .into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => pages as i64,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        let info = document.add_object(dictionary! {
+            "Title" => Object::String(title.into(), StringFormat::Literal),
+        });
+        document.trailer.set("Root", catalog);
+        document.trailer.set("Info", info);
+        let mut data = Vec::new();
+        document.save_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_pdf_summary() {
+        let data = pdf(3, "Whitepaper");
+        assert!(data.starts_with(PDF_MAGIC));
+        assert_eq!(
+            PdfSummary::parse(&data).unwrap(),
+            PdfSummary {
+                pages: 3,
+                title: Some("Whitepaper".into()),
+                author: None,
+            }
+        );
+        assert!(PdfSummary::parse(b"%PDF-1.5 truncated").is_err());
+    }
+
+    #[test]
+    fn test_classify_pdf() {
+        let data = pdf(1, "Whitepaper");
+        let opts = ParseOptions::default();
+        for mime in ["application/pdf", "text/plain"] {
+            let parsed = parse_data(&data, mime, &opts);
+            assert_eq!(parsed.kind(), "pdf");
+            assert_eq!(parsed.detected_format(), Some("pdf"));
+        }
+    }
+}
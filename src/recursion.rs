@@ -0,0 +1,50 @@
+use crate::inscription::InscriptionId;
+
+/// Find every inscription referenced recursively through `/content/<INSCRIPTION_ID>`, in order
+/// of appearance.
+pub fn recursive_refs(text: &str) -> Vec<InscriptionId> {
+    text.match_indices("/content/")
+        .filter_map(|(idx, pattern)| {
+            let rest = &text[idx + pattern.len()..];
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            rest[..end].parse().ok()
+        })
+        .collect()
+}
+
+/// Find the inscriptions referenced by the `src` of `<script>` tags in an HTML document, which
+/// is how generative art pulls in its libraries.
+pub fn script_refs(html: &str) -> Vec<InscriptionId> {
+    let lower = html.to_ascii_lowercase();
+    lower
+        .match_indices("<script")
+        .flat_map(|(start, _)| {
+            let end = lower[start..]
+                .find('>')
+                .map_or(html.len(), |end| start + end);
+            recursive_refs(&html[start..end])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "6fb976ab49dcec017f1e201e84395983204ae1a7c2abf7ced0a85d692e442799i0";
+
+    #[test]
+    fn test_recursive_refs() {
+        let html = format!(
+            r#"<html><script src="/content/{ID}"></script><img src="/content/{ID}"><script>let x = 1;</script></html>"#
+        );
+        assert_eq!(recursive_refs(&html).len(), 2);
+        let scripts = script_refs(&html);
+        assert_eq!(scripts.len(), 1);
+        assert_eq!(scripts[0].to_string(), ID);
+
+        assert!(recursive_refs("<a href=\"/content/nothing\">").is_empty());
+    }
+}
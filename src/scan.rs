@@ -1,48 +1,752 @@
-use std::sync::Arc;
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{stdin, stdout, BufRead, BufReader, Write},
+    ops::RangeInclusive,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use bitcoin::{BlockHash, Txid};
-use bitcoincore_rpc::{Client, RpcApi};
+use anyhow::{anyhow, bail};
+use bitcoin::{consensus::deserialize, Block, BlockHash, Transaction, Txid};
+use rayon::prelude::*;
+use serde_json::json;
 
 use crate::{
     args::{Args, BlockInd, ScanMode},
-    filter::Filter,
-    inscription::Inscription,
+    chain::{self, ChainSource},
+    filter::{mime_matches, Filter},
+    hash::HashAlgo,
+    imagehash::ImageHash,
+    inscription::{Inscription, ParseOptions, ParsedData},
+    output::format_size,
+    transcript::{self, differences, Classification, Recorder},
 };
 
-pub fn scan(args: &Args) -> anyhow::Result<Vec<Arc<Inscription>>> {
-    match args.scan_mode()? {
-        ScanMode::Block(block, filter) => scan_block(args, &block, &filter),
+/// A wall clock budget for a scan, set with `--deadline`. Scans check it between transactions
+/// and stop early once it is used up, keeping what they found so far.
+pub struct Deadline {
+    at: Option<Instant>,
+    reached: AtomicBool,
+}
+
+impl Deadline {
+    /// Start the clock on a budget. Without a budget the deadline is never reached.
+    pub fn new(budget: Option<Duration>) -> Self {
+        Deadline {
+            at: budget.map(|budget| Instant::now() + budget),
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    /// Check whether the budget is used up
+    pub fn check(&self) -> bool {
+        if self.at.is_some_and(|at| Instant::now() >= at) {
+            self.reached.store(true, Ordering::Relaxed);
+        }
+        self.reached.load(Ordering::Relaxed)
+    }
+
+    /// Whether a scan was stopped early because the budget ran out
+    pub fn was_reached(&self) -> bool {
+        self.reached.load(Ordering::Relaxed)
+    }
+}
+
+/// Details of a scanned block, shown as a header before its inscriptions
+pub struct BlockSummary {
+    /// Height from the coinbase, or as requested. Blocks before BIP 34 don't record it
+    pub height: Option<u64>,
+    pub hash: BlockHash,
+    /// Block timestamp, in seconds since the Unix epoch
+    pub time: u32,
+    pub tx_count: usize,
+}
+
+impl BlockSummary {
+    fn new(block: &Block, requested: &BlockInd) -> Self {
+        let height = match requested {
+            BlockInd::BlockHeight(height) => Some(*height),
+            BlockInd::BlockHash(_) => block.bip34_block_height().ok(),
+        };
+        BlockSummary {
+            height,
+            hash: block.block_hash(),
+            time: block.header.time,
+            tx_count: block.txdata.len(),
+        }
+    }
+}
+
+/// Scan the block or transaction given on the command line. Block scans also return a summary
+/// of the block.
+pub fn scan(
+    args: &Args,
+    deadline: &Deadline,
+) -> anyhow::Result<(Option<BlockSummary>, Vec<Arc<Inscription>>)> {
+    let (summary, mut inscriptions) = match args.scan_mode()? {
+        ScanMode::Block(block, filter) => {
+            let (summary, inscriptions) = scan_block(args, &block, &filter, deadline)?;
+            (Some(summary), inscriptions)
+        }
         ScanMode::Transaction(txid, block, filter) => {
-            scan_transaction(args, &txid, &block, &filter)
+            (None, scan_transaction(args, &txid, &block, &filter)?)
+        }
+        ScanMode::BlockRange(..) => bail!("Block ranges are scanned one block at a time"),
+    };
+
+    inscriptions.retain(|inscription| !is_excluded(args, inscription));
+    Ok((summary, inscriptions))
+}
+
+/// Append a record of a finished scan to `--log-file`, if set. Failing to write the log is
+/// reported on stderr but never fails the scan.
+pub fn log_scan(args: &Args, scanned: impl Display, inscriptions: usize) {
+    let Some(path) = args.log_file() else {
+        return;
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let record = json!({
+        "timestamp": timestamp,
+        "scanned": scanned.to_string(),
+        "inscriptions": inscriptions,
+        "filters": args.filters().iter().map(Filter::to_string).collect::<Vec<_>>(),
+    });
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{record}"));
+    if let Err(e) = written {
+        eprintln!("Unable to write to log file {}: {e}", path.display());
+    }
+}
+
+fn is_excluded(args: &Args, inscription: &Inscription) -> bool {
+    let excluded_mime = args
+        .exclude_mime()
+        .iter()
+        .any(|pattern| mime_matches(pattern, &inscription.mime));
+    excluded_mime || !is_allowlisted(args, inscription) || !is_similar(args, inscription)
+}
+
+/// Whether the inscription's content hash is in `--hash-allowlist`. Everything matches when the
+/// option is not set.
+fn is_allowlisted(args: &Args, inscription: &Inscription) -> bool {
+    args.hash_allowlist()
+        .is_none_or(|allowlist| allowlist.contains(&HashAlgo::Sha256.digest(&inscription.data)))
+}
+
+/// Whether an inscription is an image similar to `--similar-to`. Everything matches when the
+/// option is not set, and images are only hashed when it is.
+fn is_similar(args: &Args, inscription: &Inscription) -> bool {
+    let Some((hash, max_distance)) = args.similar_to() else {
+        return true;
+    };
+    match &inscription.parsed {
+        ParsedData::Image(image, _) => ImageHash::new(image).distance(&hash) <= max_distance,
+        _ => false,
+    }
+}
+
+/// Read newline delimited raw transaction hex from stdin until it is closed, passing every
+/// matching inscription to `emit` as soon as its transaction is parsed. Malformed lines are
+/// skipped with a note on stderr.
+pub fn scan_stdin(
+    args: &Args,
+    deadline: &Deadline,
+    mut emit: impl FnMut(Arc<Inscription>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let filters = args.filters();
+    let opts = args.parse_options();
+    let recorder = recorder(args)?;
+    for (lineno, line) in stdin().lock().lines().enumerate() {
+        if deadline.check() {
+            break;
+        }
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let tx: Transaction = match hex::decode(line)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(deserialize(&bytes)?))
+        {
+            Ok(tx) => tx,
+            Err(e) => {
+                eprintln!("Skipping line {}: {e}", lineno + 1);
+                continue;
+            }
+        };
+        for inscription in extract_recorded(&tx, &opts, recorder.as_ref())? {
+            if Filter::matches(filters, &inscription) && !is_excluded(args, &inscription) {
+                emit(inscription)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scan the transactions in a transcript written by `--record`, passing every matching
+/// inscription to `emit`. Inscriptions classified differently than when they were recorded are
+/// noted on stderr, which is what makes a transcript useful in a bug report.
+pub fn scan_replay(
+    args: &Args,
+    path: &Path,
+    deadline: &Deadline,
+    mut emit: impl FnMut(Arc<Inscription>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let filters = args.filters();
+    let opts = args.parse_options();
+    let transcript = BufReader::new(
+        File::open(path)
+            .map_err(|e| anyhow!("Unable to open transcript {}: {e}", path.display()))?,
+    );
+    for (lineno, line) in transcript.lines().enumerate() {
+        if deadline.check() {
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: transcript::Entry = serde_json::from_str(&line)
+            .map_err(|e| anyhow!("Line {} of the transcript is invalid: {e}", lineno + 1))?;
+        let tx: Transaction = deserialize(&entry.tx)?;
+        let inscriptions = match Inscription::extract_all(&tx, &opts) {
+            Ok(inscriptions) => inscriptions,
+            Err(e) => {
+                eprintln!("{}: failed to parse: {e}", tx.txid());
+                continue;
+            }
+        };
+        if let Some(e) = &entry.error {
+            eprintln!("{}: failed to parse when recorded: {e}", tx.txid());
+        }
+        let replayed: Vec<_> = inscriptions
+            .iter()
+            .map(|inscription| Classification::new(inscription))
+            .collect();
+        for note in differences(&entry.inscriptions, &replayed) {
+            eprintln!("{note}");
+        }
+        for inscription in inscriptions {
+            if Filter::matches(filters, &inscription) && !is_excluded(args, &inscription) {
+                emit(inscription)?;
+            }
         }
     }
+    Ok(())
+}
+
+/// The transcript set with `--record`, if any
+fn recorder(args: &Args) -> anyhow::Result<Option<Recorder>> {
+    args.record().map(|path| Recorder::open(path)).transpose()
+}
+
+/// Extract a transaction's inscriptions, adding it to the transcript if one is being recorded
+fn extract_recorded(
+    tx: &Transaction,
+    opts: &ParseOptions,
+    recorder: Option<&Recorder>,
+) -> anyhow::Result<Vec<Arc<Inscription>>> {
+    let extracted = Inscription::extract_all(tx, opts);
+    if let Some(recorder) = recorder {
+        recorder.record(tx, &extracted);
+    }
+    extracted
+}
+
+/// Scan the transactions in the node's mempool, up to `--limit` of them, passing each
+/// transaction's matching inscriptions to `emit` as soon as it is parsed. Transactions that leave
+/// the mempool before they are fetched are skipped.
+pub fn scan_mempool(
+    args: &Args,
+    deadline: &Deadline,
+    mut emit: impl FnMut(&Txid, Vec<Arc<Inscription>>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let rpc = chain::connect(args)?;
+    let filters = args.filters();
+    let opts = args.parse_options();
+    let recorder = recorder(args)?;
+    let txids = rpc.get_raw_mempool()?;
+    let limit = args.mempool_limit().unwrap_or(txids.len());
+    for txid in txids.iter().take(limit) {
+        if deadline.check() {
+            break;
+        }
+        let Ok(tx) = rpc.get_raw_transaction(txid, None) else {
+            continue;
+        };
+        let inscriptions = match extract_recorded(&tx, &opts, recorder.as_ref()) {
+            Ok(inscriptions) => inscriptions,
+            Err(e) => {
+                report_failures(args, &[e.context(*txid)]);
+                continue;
+            }
+        };
+        let inscriptions: Vec<_> = inscriptions
+            .into_iter()
+            .filter(|inscription| {
+                Filter::matches(filters, inscription) && !is_excluded(args, inscription)
+            })
+            .collect();
+        if !inscriptions.is_empty() {
+            emit(txid, inscriptions)?;
+        }
+    }
+    Ok(())
+}
+
+/// Scan every block listed in a file, or stdin if the path is `-`, one block hash or height per
+/// line. Each block's matching inscriptions are passed to `emit` once the block is scanned.
+/// Unparseable lines and blocks that can't be scanned are skipped with a note on stderr.
+pub fn scan_block_list(
+    args: &Args,
+    path: &Path,
+    deadline: &Deadline,
+    mut emit: impl FnMut(&BlockInd, &BlockSummary, Vec<Arc<Inscription>>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let reader: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    for (lineno, line) in reader.lines().enumerate() {
+        if deadline.check() {
+            break;
+        }
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let block: BlockInd = match line.parse() {
+            Ok(block) => block,
+            Err(e) => {
+                eprintln!("Skipping line {}: {e} ({line})", lineno + 1);
+                continue;
+            }
+        };
+        scan_one_of_many(args, &block, args.filters(), deadline, &mut emit)?;
+    }
+    Ok(())
+}
+
+/// Scan every block in a range of heights in order, passing each block's matching inscriptions
+/// to `emit`. Blocks that fail to scan are skipped with a note on stderr.
+pub fn scan_block_range(
+    args: &Args,
+    heights: RangeInclusive<u64>,
+    filters: &[Filter],
+    deadline: &Deadline,
+    mut emit: impl FnMut(&BlockInd, &BlockSummary, Vec<Arc<Inscription>>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    for height in heights {
+        if deadline.check() {
+            break;
+        }
+        scan_one_of_many(
+            args,
+            &BlockInd::BlockHeight(height),
+            filters,
+            deadline,
+            &mut emit,
+        )?;
+    }
+    Ok(())
+}
+
+/// Scan a block as part of a multi-block scan, where a block that fails is skipped rather than
+/// ending the scan
+fn scan_one_of_many(
+    args: &Args,
+    block: &BlockInd,
+    filters: &[Filter],
+    deadline: &Deadline,
+    emit: &mut impl FnMut(&BlockInd, &BlockSummary, Vec<Arc<Inscription>>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let (summary, mut inscriptions) = match scan_block(args, block, filters, deadline) {
+        Ok(scanned) => scanned,
+        Err(e) => {
+            eprintln!("Skipping block {block}: {e}");
+            return Ok(());
+        }
+    };
+    inscriptions.retain(|inscription| !is_excluded(args, inscription));
+    emit(block, &summary, inscriptions)
 }
 
 fn scan_block(
     args: &Args,
     block: &BlockInd,
     filters: &[Filter],
-) -> anyhow::Result<Vec<Arc<Inscription>>> {
-    let rpc = bitcoincore_rpc::Client::new(&args.rpc_host(), args.rpc_auth()?)?;
-    let bh = get_block_from_ind(&rpc, block)?;
+    deadline: &Deadline,
+) -> anyhow::Result<(BlockSummary, Vec<Arc<Inscription>>)> {
+    let rpc = chain::connect(args)?;
+    let requested = block;
+    let block = fetch_block(args, rpc.as_ref(), block)?;
+    let opts = args.parse_options();
+    let recorder = recorder(args)?;
+    let mut scanned = collect_inscriptions(&block.txdata, filters, deadline, |tx| {
+        extract_recorded(tx, &opts, recorder.as_ref())
+    });
+    if args.include_transfers() {
+        let transfers = collect_transfers(&block.txdata, filters, deadline, &opts, |txid| {
+            rpc.get_raw_transaction(txid, None)
+                .map_err(|e| anyhow!("Unable to look up spent transaction {txid}: {e}"))
+        });
+        scanned.inscriptions.extend(transfers.inscriptions);
+        scanned.failures.extend(transfers.failures);
+    }
+    let failures: Vec<_> = scanned
+        .failures
+        .into_iter()
+        .map(|(txid, e)| e.context(txid))
+        .collect();
+    report_failures(args, &failures);
+    Ok((BlockSummary::new(&block, requested), scanned.inscriptions))
+}
+
+/// Fetch a block to scan, refusing blocks over `--max-block-bytes` unless `--force` is set
+fn fetch_block(args: &Args, rpc: &dyn ChainSource, block: &BlockInd) -> anyhow::Result<Block> {
+    let bh = get_block_from_ind(rpc, block)?;
     let block = rpc.get_block(&bh)?;
-    let mut inscriptions = Vec::new();
-    for tx in &block.txdata {
-        for (input, _) in tx.input.iter().enumerate() {
-            for inscription in Inscription::extract_witness(tx, input)? {
-                // If any filters are specified, check if the inscription matches a filter and add it
-                // If no filters are specified, it automatically matches
-                if !filters.is_empty() {
-                    if filters.iter().any(|f| f.inscription(&inscription)) {
-                        inscriptions.push(inscription);
-                    }
-                } else {
-                    inscriptions.push(inscription);
+    if let Some(max) = args.max_block_bytes() {
+        let size = block.total_size();
+        if size > max && !args.force() {
+            bail!(
+                "Block {bh} is {}, over the limit of {}. Use --force to scan it anyway",
+                format_size(size, args.bytes),
+                format_size(max, args.bytes)
+            );
+        }
+    }
+    Ok(block)
+}
+
+/// What [`stream_block`] passes on as it scans
+pub enum Streamed<'a> {
+    /// The scanned block, before any of its inscriptions
+    Block(&'a BlockSummary),
+    Inscription(Arc<Inscription>),
+}
+
+/// Scan a single block like [`scan`], but pass its summary and then each matching inscription to
+/// `emit` as soon as it is parsed, instead of once the whole block is. Returns how many
+/// inscriptions were passed on.
+pub fn stream_block(
+    args: &Args,
+    block: &BlockInd,
+    filters: &[Filter],
+    deadline: &Deadline,
+    mut emit: impl FnMut(Streamed) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    let rpc = chain::connect(args)?;
+    let requested = block;
+    let block = fetch_block(args, rpc.as_ref(), block)?;
+    emit(Streamed::Block(&BlockSummary::new(&block, requested)))?;
+    let opts = args.parse_options();
+    let recorder = recorder(args)?;
+    let scanner = InscriptionScanner::with_extract(&block.txdata, filters, |tx| {
+        extract_recorded(tx, &opts, recorder.as_ref())
+    });
+    let mut count = 0;
+    let mut failures = Vec::new();
+    for extracted in scanner {
+        if deadline.check() {
+            break;
+        }
+        match extracted {
+            Ok(inscription) if !is_excluded(args, &inscription) => {
+                count += 1;
+                emit(Streamed::Inscription(inscription))?;
+            }
+            Ok(_) => {}
+            Err(e) => failures.push(e),
+        }
+    }
+    report_failures(args, &failures);
+    Ok(count)
+}
+
+/// Find inscriptions moved by a block's transactions. For every input spending a first output,
+/// the transaction that created it is fetched with `lookup`, and if that transaction revealed
+/// inscriptions on its first output they are reported as transferred. That is where reveals
+/// place inscriptions unless a pointer sends them to another output, and those are not followed.
+/// Only the first move after a reveal is caught this way. Each spent transaction is only fetched
+/// once. A transaction spending one that can't be fetched is recorded as a failure, and the rest
+/// of the block is still searched.
+fn collect_transfers(
+    txdata: &[Transaction],
+    filters: &[Filter],
+    deadline: &Deadline,
+    opts: &ParseOptions,
+    mut lookup: impl FnMut(&Txid) -> anyhow::Result<Transaction>,
+) -> BlockInscriptions {
+    let mut revealed: HashMap<Txid, Vec<Arc<Inscription>>> = HashMap::new();
+    let mut transfers = BlockInscriptions {
+        inscriptions: Vec::new(),
+        failures: Vec::new(),
+    };
+    for tx in txdata.iter().filter(|tx| !tx.is_coinbase()) {
+        if deadline.check() {
+            break;
+        }
+        for txin in tx
+            .input
+            .iter()
+            .filter(|txin| txin.previous_output.vout == 0)
+        {
+            let prev_txid = txin.previous_output.txid;
+            let inscriptions = match revealed.entry(prev_txid) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let prev = match lookup(&prev_txid) {
+                        Ok(prev) => prev,
+                        Err(e) => {
+                            transfers.failures.push((tx.txid(), e));
+                            continue;
+                        }
+                    };
+                    // A spent transaction that fails to parse has no inscriptions to move
+                    let inscriptions = Inscription::extract_all(&prev, opts).unwrap_or_default();
+                    entry.insert(
+                        inscriptions
+                            .into_iter()
+                            .filter(|inscription| reveal_output(&prev, inscription.pointer) == 0)
+                            .collect(),
+                    )
                 }
+            };
+            transfers.inscriptions.extend(
+                inscriptions
+                    .iter()
+                    .filter(|inscription| Filter::matches(filters, inscription))
+                    .map(|inscription| {
+                        Arc::new(Inscription {
+                            transferred_by: Some(tx.txid()),
+                            ..inscription.as_ref().clone()
+                        })
+                    }),
+            );
+        }
+    }
+    transfers
+}
+
+/// The output of a reveal transaction that an inscription is made on. A pointer names the offset
+/// of its sat among the outputs' sats. Without one, or when it points past the last output, ord
+/// uses the first sat.
+fn reveal_output(reveal: &Transaction, pointer: Option<u64>) -> usize {
+    let Some(pointer) = pointer else {
+        return 0;
+    };
+    let mut end = 0;
+    for (vout, output) in reveal.output.iter().enumerate() {
+        end += output.value.to_sat();
+        if pointer < end {
+            return vout;
+        }
+    }
+    0
+}
+
+/// Inscriptions extracted from a block, along with any transactions that failed to parse.
+pub struct BlockInscriptions {
+    pub inscriptions: Vec<Arc<Inscription>>,
+    pub failures: Vec<(Txid, anyhow::Error)>,
+}
+
+/// Fetch a block from the node or Esplora server and extract every inscription in it that matches `filters`.
+///
+/// This is part of the stable API. See [`extract_block_inscriptions`] for the filtering rules.
+pub fn scan_block_inscriptions(
+    client: &dyn ChainSource,
+    block_hash: &BlockHash,
+    filters: &[Filter],
+    opts: &ParseOptions,
+) -> anyhow::Result<BlockInscriptions> {
+    let block = client.get_block(block_hash)?;
+    Ok(extract_block_inscriptions(&block, filters, opts))
+}
+
+/// Extract every inscription in an already fetched block that matches `filters`, in block order.
+///
+/// This is part of the stable API. It performs no I/O, so callers managing their own RPC
+/// connection can reuse it. An inscription matches if it matches any of the positive filters, or
+/// if none are given, and none of the negated filters. A transaction that fails to parse is
+/// skipped and recorded in [`BlockInscriptions::failures`] rather than aborting the rest of the
+/// block.
+pub fn extract_block_inscriptions(
+    block: &Block,
+    filters: &[Filter],
+    opts: &ParseOptions,
+) -> BlockInscriptions {
+    collect_inscriptions(&block.txdata, filters, &Deadline::new(None), |tx| {
+        Inscription::extract_all(tx, opts)
+    })
+}
+
+/// Extracts a transaction's inscriptions, for [`InscriptionScanner`]
+type Extract<'a> = dyn Fn(&Transaction) -> anyhow::Result<Vec<Arc<Inscription>>> + Sync + 'a;
+
+/// Transactions parsed at once by [`InscriptionScanner`]
+const SCANNER_BATCH: usize = 64;
+
+/// Lazily extracts the inscriptions in a block that match `filters`, in block order.
+///
+/// This is part of the stable API. Transactions are parsed in small parallel batches as the
+/// iterator is advanced, so the first inscriptions arrive before the whole block is parsed, and
+/// the rest of the block is never parsed if the caller stops early. A transaction that fails to
+/// parse yields an error with its txid as context, and the scan carries on with the next one.
+pub struct InscriptionScanner<'a> {
+    txdata: &'a [Transaction],
+    filters: &'a [Filter],
+    extract: Box<Extract<'a>>,
+    scanned: usize,
+    pending: VecDeque<anyhow::Result<Arc<Inscription>>>,
+}
+
+impl<'a> InscriptionScanner<'a> {
+    pub fn new(block: &'a Block, filters: &'a [Filter], opts: &'a ParseOptions) -> Self {
+        Self::with_extract(&block.txdata, filters, |tx| {
+            Inscription::extract_all(tx, opts)
+        })
+    }
+
+    fn with_extract(
+        txdata: &'a [Transaction],
+        filters: &'a [Filter],
+        extract: impl Fn(&Transaction) -> anyhow::Result<Vec<Arc<Inscription>>> + Sync + 'a,
+    ) -> Self {
+        InscriptionScanner {
+            txdata,
+            filters,
+            extract: Box::new(extract),
+            scanned: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// How many of the block's transactions have been parsed so far
+    pub fn scanned(&self) -> usize {
+        self.scanned
+    }
+
+    fn scan_batch(&mut self) {
+        let end = (self.scanned + SCANNER_BATCH).min(self.txdata.len());
+        let batch = &self.txdata[self.scanned..end];
+        let extract = &self.extract;
+        let results: Vec<_> = batch.par_iter().map(|tx| (tx, extract(tx))).collect();
+        self.scanned = end;
+        for (tx, extracted) in results {
+            match extracted {
+                Ok(inscriptions) => self.pending.extend(
+                    inscriptions
+                        .into_iter()
+                        .filter(|inscription| Filter::matches(self.filters, inscription))
+                        .map(Ok),
+                ),
+                Err(e) => self.pending.push_back(Err(e.context(tx.txid()))),
             }
         }
     }
-    Ok(inscriptions)
+}
+
+impl Iterator for InscriptionScanner<'_> {
+    type Item = anyhow::Result<Arc<Inscription>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && self.scanned < self.txdata.len() {
+            self.scan_batch();
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Extract the inscriptions of every transaction in parallel. Results are gathered in block
+/// order, then by index within each transaction, so the output is the same on every run. Once
+/// the deadline is reached, the remaining transactions are skipped.
+fn collect_inscriptions(
+    txdata: &[Transaction],
+    filters: &[Filter],
+    deadline: &Deadline,
+    extract: impl Fn(&Transaction) -> anyhow::Result<Vec<Arc<Inscription>>> + Sync,
+) -> BlockInscriptions {
+    let results: Vec<_> = txdata
+        .par_iter()
+        .map(|tx| {
+            if deadline.check() {
+                return None;
+            }
+            let extracted = extract(tx).map(|inscriptions| {
+                inscriptions
+                    .into_iter()
+                    .filter(|inscription| Filter::matches(filters, inscription))
+                    .collect::<Vec<_>>()
+            });
+            Some((tx, extracted))
+        })
+        .collect();
+
+    let mut scanned = BlockInscriptions {
+        inscriptions: Vec::new(),
+        failures: Vec::new(),
+    };
+    for (tx, extracted) in results.into_iter().flatten() {
+        match extracted {
+            Ok(inscriptions) => scanned.inscriptions.extend(inscriptions),
+            Err(e) => scanned.failures.push((tx.txid(), e)),
+        }
+    }
+    scanned
+}
+
+/// Check whether a block has at least one inscription matching the filters, stopping at the
+/// first match
+pub fn block_has_inscription(args: &Args, block: &BlockInd) -> anyhow::Result<bool> {
+    let rpc = chain::connect(args)?;
+    let block = rpc.get_block(&get_block_from_ind(rpc.as_ref(), block)?)?;
+    Ok(contains_inscription(
+        &block.txdata,
+        args.filters(),
+        &args.parse_options(),
+    ))
+}
+
+fn contains_inscription(txdata: &[Transaction], filters: &[Filter], opts: &ParseOptions) -> bool {
+    txdata.iter().any(|tx| {
+        (0..tx.input.len()).any(|input| {
+            Inscription::extract_witness(tx, input, opts)
+                .unwrap_or_default()
+                .iter()
+                .any(|inscription| Filter::matches(filters, inscription))
+        })
+    })
+}
+
+/// Report transactions that were skipped because they failed to parse, or because a transaction
+/// they spend couldn't be looked up. Each error has the transaction's txid as context.
+fn report_failures(args: &Args, failures: &[anyhow::Error]) {
+    if failures.is_empty() {
+        return;
+    }
+    if args.verbose {
+        for e in failures {
+            eprintln!("Skipped {e:#}");
+        }
+    }
+    eprintln!("{} transaction(s) skipped", failures.len());
 }
 
 fn scan_transaction(
@@ -51,28 +755,251 @@ fn scan_transaction(
     block: &Option<BlockInd>,
     filters: &[Filter],
 ) -> anyhow::Result<Vec<Arc<Inscription>>> {
-    let rpc = bitcoincore_rpc::Client::new(&args.rpc_host(), args.rpc_auth()?)?;
-    let bh = block.map(|bh| get_block_from_ind(&rpc, &bh).ok()).flatten();
+    let rpc = chain::connect(args)?;
+    let bh = block
+        .map(|bh| get_block_from_ind(rpc.as_ref(), &bh).ok())
+        .flatten();
     let tx = rpc.get_raw_transaction(txid, bh.as_ref())?;
-    let inscriptions = Inscription::extract_all(&tx)?;
+    if let Some(path) = args.raw_witness_hex() {
+        if path == Path::new("-") {
+            write_witness_hex(&tx, &mut stdout().lock())?;
+        } else {
+            write_witness_hex(&tx, &mut File::create(path)?)?;
+        }
+    }
+    let inscriptions = extract_recorded(&tx, &args.parse_options(), recorder(args)?.as_ref())?;
     let inscriptions: Vec<Arc<Inscription>> = inscriptions
         .into_iter()
-        .filter(|inscription| {
-            // If any filters are specified, check if the inscription matches a filter and add it
-            // If no filters are specified, it automatically matches
-            if !filters.is_empty() {
-                filters.iter().any(|f| f.inscription(inscription))
-            } else {
-                true
-            }
-        })
+        .filter(|inscription| Filter::matches(filters, inscription))
         .collect();
     Ok(inscriptions)
 }
 
-fn get_block_from_ind(client: &Client, blockind: &BlockInd) -> anyhow::Result<BlockHash> {
+/// Write every element of every input's witness stack as hex, one element per line under a
+/// header for its input
+fn write_witness_hex(tx: &Transaction, out: &mut impl Write) -> anyhow::Result<()> {
+    for (idx, txin) in tx.input.iter().enumerate() {
+        writeln!(out, "input {idx}:")?;
+        for (element, data) in txin.witness.iter().enumerate() {
+            writeln!(out, "  {element}: {}", hex::encode(data))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn get_block_from_ind(client: &dyn ChainSource, blockind: &BlockInd) -> anyhow::Result<BlockHash> {
     Ok(match blockind {
         BlockInd::BlockHash(bh) => *bh,
         BlockInd::BlockHeight(bh) => client.get_block_hash(*bh)?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use bitcoin::{Amount, OutPoint, ScriptBuf, TxIn, TxOut, Witness};
+
+    use super::*;
+    use crate::inscription::tests::inscription_tx;
+
+    #[test]
+    fn test_inscription_scanner() {
+        let bodies: Vec<_> = (0..200).map(|i| format!("inscription {i}")).collect();
+        let txdata: Vec<_> = bodies
+            .iter()
+            .map(|body| inscription_tx(&[body.as_bytes()]))
+            .collect();
+        let opts = ParseOptions::default();
+        let parsed = std::sync::atomic::AtomicUsize::new(0);
+        let mut scanner = InscriptionScanner::with_extract(&txdata, &[], |tx| {
+            parsed.fetch_add(1, Ordering::SeqCst);
+            Inscription::extract_all(tx, &opts)
+        });
+
+        // Stopping early leaves the rest of the block unparsed
+        let first: Vec<_> = scanner.by_ref().take(3).map(Result::unwrap).collect();
+        assert_eq!(first[0].data, bodies[0].as_bytes());
+        assert_eq!(first[2].data, bodies[2].as_bytes());
+        assert_eq!(scanner.scanned(), SCANNER_BATCH);
+        assert_eq!(parsed.load(Ordering::SeqCst), SCANNER_BATCH);
+
+        // The rest arrive in block order
+        let rest: Vec<_> = scanner.map(Result::unwrap).collect();
+        assert_eq!(rest.len(), 197);
+        assert_eq!(rest[196].data, bodies[199].as_bytes());
+        assert_eq!(parsed.load(Ordering::SeqCst), 200);
+    }
+
+    #[test]
+    fn test_write_witness_hex() {
+        let mut tx = inscription_tx(&[b"hi"]);
+        tx.input[0].witness = Witness::from_slice(&[vec![0xab, 0xcd], vec![], vec![0xc0]]);
+        let mut out = Vec::new();
+        write_witness_hex(&tx, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "input 0:\n  0: abcd\n  1: \n  2: c0\n"
+        );
+    }
+
+    #[test]
+    fn test_contains_inscription() {
+        let opts = ParseOptions::default();
+        let mut plain = inscription_tx(&[]);
+        plain.input[0].witness = Witness::new();
+        let txdata = vec![plain.clone(), inscription_tx(&[b"hello"])];
+        assert!(contains_inscription(&txdata, &[], &opts));
+        assert!(contains_inscription(&txdata, &[Filter::Text], &opts));
+        assert!(!contains_inscription(&txdata, &[Filter::Image], &opts));
+        assert!(!contains_inscription(&[plain], &[], &opts));
+    }
+
+    #[test]
+    fn test_deadline_stops_block() {
+        let txdata = vec![inscription_tx(&[b"first"]), inscription_tx(&[b"second"])];
+        let deadline = Deadline::new(Some(Duration::ZERO));
+        let scanned = collect_inscriptions(&txdata, &[], &deadline, |tx| {
+            Inscription::extract_all(tx, &ParseOptions::default())
+        });
+        assert!(scanned.inscriptions.is_empty());
+        assert!(deadline.was_reached());
+
+        let unlimited = Deadline::new(None);
+        assert!(!unlimited.check());
+        assert!(!unlimited.was_reached());
+    }
+
+    #[test]
+    fn test_failed_tx_does_not_abort_block() {
+        let txdata = vec![
+            inscription_tx(&[b"first"]),
+            inscription_tx(&[b"broken"]),
+            inscription_tx(&[b"third"]),
+        ];
+        let broken = txdata[1].wtxid();
+        let scanned = collect_inscriptions(&txdata, &[], &Deadline::new(None), |tx| {
+            if tx.wtxid() == broken {
+                return Err(anyhow!("Parse error"));
+            }
+            Inscription::extract_all(tx, &ParseOptions::default())
+        });
+        let data: Vec<_> = scanned.inscriptions.iter().map(|i| &i.data[..]).collect();
+        assert_eq!(data, [&b"first"[..], &b"third"[..]]);
+        assert_eq!(scanned.failures.len(), 1);
+        assert_eq!(scanned.failures[0].0, txdata[1].txid());
+    }
+
+    #[test]
+    fn test_parallel_block_order() {
+        // A synthetic full block, large enough that the work is split across threads
+        let txdata: Vec<_> = (0..2000u32)
+            .map(|n| inscription_tx(&[&n.to_le_bytes(), b"second", b"third"]))
+            .collect();
+        let opts = ParseOptions::default();
+        let expected: Vec<_> = txdata
+            .iter()
+            .flat_map(|tx| Inscription::extract_all(tx, &opts).unwrap())
+            .map(|inscription| (inscription.txid, inscription.index))
+            .collect();
+        assert_eq!(expected.len(), 6000);
+        for _ in 0..3 {
+            let scanned = collect_inscriptions(&txdata, &[], &Deadline::new(None), |tx| {
+                Inscription::extract_all(tx, &opts)
+            });
+            let found: Vec<_> = scanned
+                .inscriptions
+                .iter()
+                .map(|inscription| (inscription.txid, inscription.index))
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_collect_transfers() {
+        let reveal = inscription_tx(&[b"moved"]);
+        let spend = |vouts: &[u32]| {
+            let mut tx = inscription_tx(&[]);
+            tx.input = vouts
+                .iter()
+                .map(|&vout| TxIn {
+                    previous_output: OutPoint::new(reveal.txid(), vout),
+                    witness: Witness::new(),
+                    ..tx.input[0].clone()
+                })
+                .collect();
+            tx
+        };
+        // The second transaction only spends an output the inscription was not revealed on
+        let txdata = vec![spend(&[0]), spend(&[1]), spend(&[0, 2])];
+        let mut lookups = 0;
+        let transfers = collect_transfers(
+            &txdata,
+            &[],
+            &Deadline::new(None),
+            &ParseOptions::default(),
+            |_| {
+                lookups += 1;
+                Ok(reveal.clone())
+            },
+        )
+        .inscriptions;
+        assert_eq!(lookups, 1);
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].data, b"moved");
+        assert_eq!(transfers[0].txid, reveal.txid());
+        assert_eq!(transfers[0].transferred_by, Some(txdata[0].txid()));
+        assert_eq!(transfers[1].transferred_by, Some(txdata[2].txid()));
+
+        let filtered = collect_transfers(
+            &txdata,
+            &[Filter::Image],
+            &Deadline::new(None),
+            &ParseOptions::default(),
+            |_| Ok(reveal.clone()),
+        );
+        assert!(filtered.inscriptions.is_empty());
+
+        // A spent transaction that can't be fetched skips its spender, not the block
+        let mut missing = spend(&[0]);
+        missing.input[0].previous_output.txid = txdata[0].txid();
+        let txdata = vec![missing, spend(&[0])];
+        let partial = collect_transfers(
+            &txdata,
+            &[],
+            &Deadline::new(None),
+            &ParseOptions::default(),
+            |txid| {
+                if *txid == reveal.txid() {
+                    Ok(reveal.clone())
+                } else {
+                    Err(anyhow!("No such transaction"))
+                }
+            },
+        );
+        assert_eq!(partial.inscriptions.len(), 1);
+        assert_eq!(
+            partial.inscriptions[0].transferred_by,
+            Some(txdata[1].txid())
+        );
+        assert_eq!(partial.failures.len(), 1);
+        assert_eq!(partial.failures[0].0, txdata[0].txid());
+    }
+
+    #[test]
+    fn test_reveal_output() {
+        let mut reveal = inscription_tx(&[]);
+        reveal.output = [1000, 546]
+            .map(|sats| TxOut {
+                value: Amount::from_sat(sats),
+                script_pubkey: ScriptBuf::new(),
+            })
+            .to_vec();
+        assert_eq!(reveal_output(&reveal, None), 0);
+        assert_eq!(reveal_output(&reveal, Some(999)), 0);
+        assert_eq!(reveal_output(&reveal, Some(1000)), 1);
+        // Past the last output, the pointer is ignored
+        assert_eq!(reveal_output(&reveal, Some(1546)), 0);
+    }
+}
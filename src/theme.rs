@@ -0,0 +1,68 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
+use colored_json::{Color, Style, Styler};
+
+/// Built-in color schemes for printing JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Default,
+    Monokai,
+    Solarized,
+}
+
+impl Theme {
+    pub fn styler(&self) -> Styler {
+        match self {
+            Theme::Default => Styler::default(),
+            Theme::Monokai => Styler {
+                object_brackets: Style::new(Color::RGB(248, 248, 242)).bold(),
+                array_brackets: Style::new(Color::RGB(248, 248, 242)).bold(),
+                key: Style::new(Color::RGB(249, 38, 114)),
+                string_value: Style::new(Color::RGB(230, 219, 116)),
+                integer_value: Style::new(Color::RGB(174, 129, 255)),
+                float_value: Style::new(Color::RGB(174, 129, 255)),
+                bool_value: Style::new(Color::RGB(102, 217, 239)),
+                nil_value: Style::new(Color::RGB(102, 217, 239)),
+                ..Default::default()
+            },
+            // Solarized accent colors read well on both its light and dark backgrounds
+            Theme::Solarized => Styler {
+                object_brackets: Style::new(Color::RGB(101, 123, 131)).bold(),
+                array_brackets: Style::new(Color::RGB(101, 123, 131)).bold(),
+                key: Style::new(Color::RGB(38, 139, 210)),
+                string_value: Style::new(Color::RGB(42, 161, 152)),
+                integer_value: Style::new(Color::RGB(211, 54, 130)),
+                float_value: Style::new(Color::RGB(211, 54, 130)),
+                bool_value: Style::new(Color::RGB(181, 137, 0)),
+                nil_value: Style::new(Color::RGB(203, 75, 22)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Default => f.write_str("default"),
+            Theme::Monokai => f.write_str("monokai"),
+            Theme::Solarized => f.write_str("solarized"),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let theme = match s.to_lowercase().as_ref() {
+            "default" => Self::Default,
+            "monokai" => Self::Monokai,
+            "solarized" => Self::Solarized,
+            _ => return Err(anyhow!("Unknown theme")),
+        };
+        Ok(theme)
+    }
+}
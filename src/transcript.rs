@@ -0,0 +1,173 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use bitcoin::{consensus::serialize, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::inscription::Inscription;
+
+/// One scanned transaction in a transcript, stored as a JSON line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// The raw transaction
+    #[serde(with = "hex")]
+    pub tx: Vec<u8>,
+    pub inscriptions: Vec<Classification>,
+    /// Why the transaction failed to parse, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How an inscription was classified when the transcript was recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Classification {
+    pub id: String,
+    pub mime: String,
+    pub kind: String,
+    pub size: usize,
+}
+
+impl Classification {
+    pub fn new(inscription: &Inscription) -> Self {
+        Classification {
+            id: inscription.inscription_id(),
+            mime: inscription.mime.clone(),
+            kind: inscription.parsed.kind().into(),
+            size: inscription.data.len(),
+        }
+    }
+}
+
+impl Entry {
+    pub fn new(tx: &Transaction, extracted: &anyhow::Result<Vec<Arc<Inscription>>>) -> Self {
+        let (inscriptions, error) = match extracted {
+            Ok(inscriptions) => (
+                inscriptions
+                    .iter()
+                    .map(|inscription| Classification::new(inscription))
+                    .collect(),
+                None,
+            ),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        Entry {
+            tx: serialize(tx),
+            inscriptions,
+            error,
+        }
+    }
+}
+
+/// Appends scanned transactions to a transcript set with `--record`. Transactions of a block are
+/// parsed in parallel, so they are written in the order they finish rather than block order.
+pub struct Recorder {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("Unable to open transcript {}: {e}", path.display()))?;
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Record a transaction and what was extracted from it. Failing to write is reported on
+    /// stderr but never fails the scan.
+    pub fn record(&self, tx: &Transaction, extracted: &anyhow::Result<Vec<Arc<Inscription>>>) {
+        let written = serde_json::to_string(&Entry::new(tx, extracted))
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(writeln!(file, "{line}")?)
+            });
+        if let Err(e) = written {
+            eprintln!("Unable to write to transcript {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Describe how a replayed transaction's inscriptions differ from the recorded ones
+pub fn differences(recorded: &[Classification], replayed: &[Classification]) -> Vec<String> {
+    let mut notes = Vec::new();
+    for was in recorded {
+        match replayed.iter().find(|now| now.id == was.id) {
+            None => notes.push(format!("{} is no longer found", was.id)),
+            Some(now) if now.kind != was.kind => notes.push(format!(
+                "{} was classified as {}, now {}",
+                was.id, was.kind, now.kind
+            )),
+            Some(now) if now.size != was.size || now.mime != was.mime => notes.push(format!(
+                "{} was {} bytes of {}, now {} bytes of {}",
+                was.id, was.size, was.mime, now.size, now.mime
+            )),
+            Some(_) => {}
+        }
+    }
+    for now in replayed {
+        if !recorded.iter().any(|was| was.id == now.id) {
+            notes.push(format!("{} is newly found, as {}", now.id, now.kind));
+        }
+    }
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::deserialize;
+
+    use super::*;
+    use crate::inscription::{tests::inscription_tx, ParseOptions};
+
+    #[test]
+    fn test_entry_round_trip() {
+        let tx = inscription_tx(&[b"hello", b"{\"a\":1}"]);
+        let extracted = Inscription::extract_all(&tx, &ParseOptions::default());
+        let line = serde_json::to_string(&Entry::new(&tx, &extracted)).unwrap();
+        let entry: Entry = serde_json::from_str(&line).unwrap();
+        assert_eq!(deserialize::<Transaction>(&entry.tx).unwrap(), tx);
+        let kinds: Vec<_> = entry.inscriptions.iter().map(|c| &c.kind[..]).collect();
+        assert_eq!(kinds, ["text", "json"]);
+        assert!(entry.error.is_none());
+        assert!(!line.contains("error"));
+    }
+
+    #[test]
+    fn test_differences() {
+        let text = Classification {
+            id: "abci0".into(),
+            mime: "text/plain".into(),
+            kind: "text".into(),
+            size: 5,
+        };
+        let json = Classification {
+            kind: "json".into(),
+            ..text.clone()
+        };
+        let other = Classification {
+            id: "abci1".into(),
+            ..text.clone()
+        };
+        let recorded = std::slice::from_ref(&text);
+        assert!(differences(recorded, recorded).is_empty());
+        assert_eq!(
+            differences(recorded, &[json]),
+            ["abci0 was classified as text, now json"]
+        );
+        assert_eq!(
+            differences(recorded, &[other]),
+            ["abci0 is no longer found", "abci1 is newly found, as text"]
+        );
+    }
+}